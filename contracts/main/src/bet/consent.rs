@@ -0,0 +1,256 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+use near_sdk::Timestamp;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Refused,
+}
+
+/// A bet someone wants to open on a person with a linked account. `new_stake` only opens it once
+/// the linked account approved exactly this prediction and end.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BetApproval {
+    pub person: Person,
+    pub prediction: Prediction,
+    pub end: Timestamp,
+    pub requester: AccountId,
+    pub status: ApprovalStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedBetApproval {
+    pub bet_id: BetId,
+    pub person: Person,
+    pub prediction: Prediction,
+    pub end: Timestamp,
+    pub requester: AccountId,
+    pub status: ApprovalStatus,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Links the account a person controls to their entry. An admin links it once the person is
+    /// verified, after that only the linked account can move the link.
+    #[payable]
+    pub fn link_person_account(&mut self, person: Person, account_id: AccountId) -> WrappedPerson {
+        assert_one_yocto();
+        let mut record = self.persons.get(&person).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST person: ".to_owned() + &person).as_str())
+        });
+        if record.account_id != Some(env::predecessor_account_id()) {
+            self.assert_role(Role::Moderator);
+        }
+
+        record.account_id = Some(account_id);
+        self.persons.insert(&person, &record);
+
+        self.person(person).unwrap()
+    }
+
+    /// Asks a person's linked account to approve a bet before anyone stakes on it.
+    #[payable]
+    pub fn request_bet_approval(
+        &mut self,
+        bet_id: BetId,
+        person: Person,
+        prediction: Prediction,
+        end: Timestamp,
+    ) -> WrappedBetApproval {
+        let initial_storage_usage = env::storage_usage();
+        if !is_valid_id(bet_id.as_bytes()) {
+            env::panic_str(("ERR_INVALID bet_id: ".to_owned() + &bet_id).as_str());
+        }
+        if self.bets.get(&bet_id).is_some() || self.bet_approvals.get(&bet_id).is_some() {
+            env::panic_str(("ERR_EXISTS bet_id: ".to_owned() + &bet_id).as_str());
+        }
+        let record = self.persons.get(&person).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST person: ".to_owned() + &person).as_str())
+        });
+        if record.account_id.is_none() {
+            env::panic_str(("ERR_NOT_LINKED person: ".to_owned() + &person).as_str());
+        }
+        if record.status != PersonStatus::Active {
+            env::panic_str(("ERR_PERSON_INACTIVE person: ".to_owned() + &person).as_str());
+        }
+        prediction.assert_valid();
+
+        let approval = BetApproval {
+            person,
+            prediction,
+            end,
+            requester: env::predecessor_account_id(),
+            status: ApprovalStatus::Pending,
+        };
+        self.bet_approvals.insert(&bet_id, &approval);
+
+        internal::refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        wrap_approval(bet_id, approval)
+    }
+
+    #[payable]
+    pub fn approve_bet(&mut self, bet_id: BetId) -> WrappedBetApproval {
+        self.internal_answer_approval(bet_id, ApprovalStatus::Approved)
+    }
+
+    #[payable]
+    pub fn refuse_bet(&mut self, bet_id: BetId) -> WrappedBetApproval {
+        self.internal_answer_approval(bet_id, ApprovalStatus::Refused)
+    }
+
+    /// Withdraws a person from the contract: no stake is taken on them any more and every bet on
+    /// them that is not settling yet is voided, refunding each stake in full. The first
+    /// `MAX_VOIDED_BETS_PER_CALL` bets are voided right away, `void_person_bets` or `settle_bet`
+    /// void the rest.
+    #[payable]
+    pub fn opt_out(&mut self, person: Person) -> Vec<BetId> {
+        assert_one_yocto();
+        let mut record = self.persons.get(&person).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST person: ".to_owned() + &person).as_str())
+        });
+        if record.account_id != Some(env::predecessor_account_id()) {
+            env::panic_str("ERR_NOT_AUTHORIZED");
+        }
+        if record.status == PersonStatus::TakenDown {
+            env::panic_str(("ERR_TAKEN_DOWN person: ".to_owned() + &person).as_str());
+        }
+
+        record.status = PersonStatus::OptedOut;
+        self.persons.insert(&person, &record);
+
+        self.internal_void_person_bets(&person, 0, MAX_VOIDED_BETS_PER_CALL)
+    }
+
+    /// Voids the next `limit` bets of a person who opted out or was taken down, starting at
+    /// `from_index` of their bets. Anyone can call it. Returns the bets it voided.
+    pub fn void_person_bets(&mut self, person: Person, from_index: Option<U64>, limit: Option<u64>) -> Vec<BetId> {
+        if !self.internal_person_withdrawn(&person) {
+            env::panic_str(("ERR_NOT_WITHDRAWN person: ".to_owned() + &person).as_str());
+        }
+        let limit = limit.unwrap_or(MAX_VOIDED_BETS_PER_CALL).min(MAX_VOIDED_BETS_PER_CALL);
+
+        self.internal_void_person_bets(&person, u64::from(from_index.unwrap_or(U64(0))), limit)
+    }
+
+    pub fn bet_approvals(
+        &self,
+        person: Option<Person>,
+        status: Option<ApprovalStatus>,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedBetApproval> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.bet_approvals
+            .iter()
+            .filter(|(_, approval)| {
+                (person.is_none() || person.as_ref() == Some(&approval.person))
+                    && (status.is_none() || status.as_ref() == Some(&approval.status))
+            })
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(bet_id, approval)| wrap_approval(bet_id, approval))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Panics unless the person takes stakes, and for a new bet on a person with a linked account,
+    /// unless that account approved this very bet.
+    pub(crate) fn internal_assert_consent(
+        &self,
+        bet_id: &BetId,
+        person: &Person,
+        prediction: &Prediction,
+        end: Timestamp,
+        new_bet: bool,
+    ) {
+        let record = match self.persons.get(person) {
+            Some(record) => record,
+            None => return,
+        };
+        match record.status {
+            PersonStatus::OptedOut => env::panic_str(("ERR_OPTED_OUT person: ".to_owned() + person).as_str()),
+            PersonStatus::TakenDown => env::panic_str(("ERR_TAKEN_DOWN person: ".to_owned() + person).as_str()),
+            _ => (),
+        }
+        if !new_bet || record.account_id.is_none() {
+            return;
+        }
+        let approved = matches!(
+            self.bet_approvals.get(bet_id),
+            Some(approval) if approval.status == ApprovalStatus::Approved
+                && &approval.person == person
+                && &approval.prediction == prediction
+                && approval.end == end
+        );
+        if !approved {
+            env::panic_str(("ERR_NOT_APPROVED bet_id: ".to_owned() + bet_id).as_str());
+        }
+    }
+
+    /// Voids a page of a person's bets, those already settling are skipped. Voiding leaves the
+    /// index as it is, so pages stay put between calls.
+    pub(crate) fn internal_void_person_bets(&mut self, person: &Person, from_index: u64, limit: u64) -> Vec<BetId> {
+        let bet_ids: Vec<BetId> = match self.bets_by_person.get(person) {
+            Some(bet_ids) => (from_index..bet_ids.len().min(from_index + limit))
+                .map(|index| bet_ids.as_vector().get(index).unwrap())
+                .collect(),
+            None => vec![],
+        };
+
+        bet_ids
+            .into_iter()
+            .filter(|bet_id| self.internal_void_bet(bet_id))
+            .collect()
+    }
+
+    /// Whether the person opted out or was taken down, their bets are voided rather than settled.
+    pub(crate) fn internal_person_withdrawn(&self, person: &Person) -> bool {
+        matches!(
+            self.persons.get(person),
+            Some(record) if matches!(record.status, PersonStatus::OptedOut | PersonStatus::TakenDown)
+        )
+    }
+
+    fn internal_answer_approval(&mut self, bet_id: BetId, status: ApprovalStatus) -> WrappedBetApproval {
+        assert_one_yocto();
+        let mut approval = self.bet_approvals.get(&bet_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST bet_id: ".to_owned() + &bet_id).as_str())
+        });
+        let record = self.persons.get(&approval.person).unwrap();
+        if record.account_id != Some(env::predecessor_account_id()) {
+            env::panic_str("ERR_NOT_AUTHORIZED");
+        }
+        if self.bets.get(&bet_id).is_some() {
+            env::panic_str(("ERR_EXISTS bet_id: ".to_owned() + &bet_id).as_str());
+        }
+
+        approval.status = status;
+        self.bet_approvals.insert(&bet_id, &approval);
+
+        wrap_approval(bet_id, approval)
+    }
+}
+
+fn wrap_approval(bet_id: BetId, approval: BetApproval) -> WrappedBetApproval {
+    WrappedBetApproval {
+        bet_id,
+        person: approval.person,
+        prediction: approval.prediction,
+        end: approval.end,
+        requester: approval.requester,
+        status: approval.status,
+    }
+}
@@ -18,11 +18,7 @@ impl Contract {
         let mut bets: Vec<BetId>;
 
         if let Some(person) = person {
-            bets = self.bets.to_vec()
-            .iter()
-            .filter(|(_, bet)| bet.person == person)
-            .map(|(bet_id, _)| bet_id.to_string())
-            .collect();
+            bets = self.bets_by_person.get(&person).map_or_else(Vec::new, |bet_ids| bet_ids.to_vec());
         } else {
             bets = self.bets.keys_as_vector()
                 .iter()
@@ -47,11 +43,15 @@ impl Contract {
         bets.iter()
             .map(|bet_id| {
                 let bet = self.bets.get(&bet_id).unwrap();
+                let prices = self.internal_bet_prices(bet_id);
                 WrappedBet {
                     bet_id: bet_id.to_string(),
                     prediction: bet.clone().prediction,
                     person: bet.clone().person,
                     end: bet.clone().end,
+                    best_back: prices.back.first().map(|(odds, _)| *odds),
+                    best_lay: prices.lay.first().map(|(odds, _)| *odds),
+                    matching_policy: bet.matching_policy,
                 }
             })
             .filter(|wrapped_bet| {
@@ -66,6 +66,12 @@ impl Contract {
             })
             .collect()
     }
+    pub fn prices(&self, bet_ids: Vec<BetId>) -> Vec<BetPrices> {
+        bet_ids
+            .iter()
+            .map(|bet_id| self.internal_bet_prices(bet_id))
+            .collect()
+    }
     pub fn bets_count(&self) -> U64 {
         U64::from(self.bets.len())
     }
@@ -83,7 +89,7 @@ impl Contract {
     ) -> Vec<Person> {
         if let Some(exclude) = exclude {
             let mut taken = 0;
-            self.persons.iter()
+            self.persons.keys()
                 .take_while(|person| {
                     if !exclude.contains(person) {
                         taken += 1;
@@ -97,7 +103,7 @@ impl Contract {
             let start = u128::from(from_index.unwrap_or(U128(0)));
 
             //iterate through the keys vector
-            self.persons.iter()
+            self.persons.keys()
                 //skip to the index we specified in the start variable
                 .skip(start as usize)
                 //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
@@ -129,6 +135,8 @@ impl Contract {
                 staker: stake.staker,
                 unmatched: U128(stake.unmatched),
                 gentlemans: stake.gentlemans,
+                pool: stake.pool,
+                odds: stake.odds,
             })
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
@@ -143,40 +151,68 @@ impl Contract {
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
-        //iterate through the keys vector
-        self.stakes
+        //iterate through the stakes indexed under each bet
+        bet_ids
             .iter()
-            .filter(|(_, stake)| bet_ids.contains(&stake.bet_id))
+            .flat_map(|bet_id| self.internal_bet_stake_ids(bet_id, None, None))
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
-            .map(|(key, stake)| WrappedStake {
-                stake_id: key,
-                bet_id: stake.bet_id,
-                position: stake.position,
-                amount: U128(stake.amount),
-                staker: stake.staker,
-                unmatched: U128(stake.unmatched),
-                gentlemans: stake.gentlemans,
+            .map(|key| {
+                let stake = self.stakes.get(&key).unwrap();
+                WrappedStake {
+                    stake_id: key,
+                    bet_id: stake.bet_id,
+                    position: stake.position,
+                    amount: U128(stake.amount),
+                    staker: stake.staker,
+                    unmatched: U128(stake.unmatched),
+                    gentlemans: stake.gentlemans,
+                    pool: stake.pool,
+                    odds: stake.odds,
+                }
             })
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
+    pub fn cancelled_stakes(
+        &self,
+        stake_ids: Vec<StakeId>
+    ) -> Vec<WrappedCancelledStake> {
+        stake_ids
+            .into_iter()
+            .filter_map(|stake_id| {
+                self.cancelled_stakes.get(&stake_id).map(|cancelled_stake| WrappedCancelledStake {
+                    stake_id,
+                    bet_id: cancelled_stake.bet_id,
+                    amount: U128(cancelled_stake.amount),
+                    staker: cancelled_stake.staker,
+                    epoch: cancelled_stake.epoch,
+                    status: cancelled_stake.status,
+                    pending: cancelled_stake.pending,
+                    pool: cancelled_stake.pool,
+                })
+            })
+            .collect()
+    }
+
     pub fn stake_earnings(
         &self,
         stake_ids: Vec<StakeId>
     ) -> Vec<WrappedStakeEarning> {
         stake_ids
             .into_iter()
-            .map(|stake_id| {
+            .filter_map(|stake_id| {
                 let stake = self.stakes.get(&stake_id).unwrap();
                 let account = self.accounts.get(&stake.staker).unwrap();
-                let stake_earning = account.earnings.get(&stake_id.clone()).unwrap();
+                // settled stakes paid their earnings out and no longer keep them
+                let stake_earning = account.earnings.get(&stake_id.clone())?;
                 let bet = self.bets.get(&stake.bet_id.clone()).unwrap();
+                let yield_balance = self.internal_stake_yield(&stake_id, &stake);
 
-                WrappedStakeEarning {
+                Some(WrappedStakeEarning {
                     stake_id: stake_id.clone(),
                     bet_id: stake.bet_id,
                     prediction: bet.prediction,
@@ -187,9 +223,9 @@ impl Contract {
                     gentlemans: stake.gentlemans,
                     epoch: stake.epoch,
                     epochs: stake_earning.epochs,
-                    yield_balance: U128(stake_earning.yield_balance),
+                    yield_balance: U128(yield_balance),
                     total_balance: U128::from(stake.amount),
-                }
+                })
             })
             .collect()
     }
@@ -204,14 +240,22 @@ impl Contract {
         let start = u128::from(from_index.unwrap_or(U128(0)));
         let mut bet_ids: Vec<BetId> = vec!();
 
+        let stake_ids = match self.stakes_by_account.get(&staker) {
+            Some(stake_ids) => stake_ids,
+            None => return (vec![], vec![]),
+        };
+
         //iterate through the keys vector
-        let wrapped_stakes = self.stakes
+        let wrapped_stakes = stake_ids
             .iter()
-            .filter(|(_, stake)| staker == stake.staker)
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
+            .map(|key| {
+                let stake = self.stakes.get(&key).unwrap();
+                (key, stake)
+            })
             .map(|(key, stake)| {
                 if !bet_ids.contains(&stake.bet_id) {
                     bet_ids.push(stake.bet_id.clone());
@@ -224,6 +268,8 @@ impl Contract {
                     staker: stake.staker,
                     unmatched: U128(stake.unmatched),
                     gentlemans: stake.gentlemans,
+                    pool: stake.pool,
+                    odds: stake.odds,
                 }
             })
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
@@ -232,14 +278,49 @@ impl Contract {
             .iter()
             .map(|bet_id| {
                 let bet = self.bets.get(&bet_id).unwrap();
+                let prices = self.internal_bet_prices(bet_id);
                 WrappedBet {
                     bet_id: bet_id.to_string(),
                     prediction: bet.clone().prediction,
                     person: bet.clone().person,
                     end: bet.clone().end,
+                    best_back: prices.back.first().map(|(odds, _)| *odds),
+                    best_lay: prices.lay.first().map(|(odds, _)| *odds),
+                    matching_policy: bet.matching_policy,
                 }
             })
             .collect();
         return (wrapped_stakes, wrapped_bets)
     }
 }
+
+impl Contract {
+    pub(crate) fn internal_bet_prices(&self, bet_id: &BetId) -> BetPrices {
+        let mut back: Vec<(Odds, U128)> = vec![];
+        let mut lay: Vec<(Odds, U128)> = vec![];
+
+        for (_, stake) in self.internal_bet_stakes(bet_id, None, Some(false)) {
+            if stake.unmatched == 0 || stake.gentlemans || stake.settled {
+                continue;
+            }
+            // depth is quoted in backer stake, a lay's liability covers liability / (odds - 1) of it
+            let (levels, available) = match stake.position {
+                Position::Lay => (&mut back, matched_amounts(Balance::MAX, stake.unmatched, stake.odds).0),
+                Position::Back => (&mut lay, stake.unmatched),
+            };
+            if let Some(level) = levels.iter_mut().find(|(odds, _)| *odds == stake.odds) {
+                level.1 = U128(level.1.0 + available);
+            } else {
+                levels.push((stake.odds, U128(available)));
+            }
+        }
+        back.sort_by_key(|(odds, _)| std::cmp::Reverse(*odds));
+        lay.sort_by_key(|(odds, _)| *odds);
+
+        BetPrices {
+            bet_id: bet_id.clone(),
+            back,
+            lay,
+        }
+    }
+}
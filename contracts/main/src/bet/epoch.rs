@@ -0,0 +1,132 @@
+use crate::*;
+
+use near_sdk::json_types::U128;
+
+/// What the contract held across all pools at the start of an epoch: principal, attributed yield
+/// and anything unstaked but not yet withdrawn. One fixed-size record per epoch, what each account
+/// held is kept per account instead, see `account_balances`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochBalances {
+    pub total_balance: Balance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedEpochBalances {
+    pub epoch: EpochHeight,
+    pub total_balance: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Keeper entry point, records the current epoch's total unless a stake or cancellation
+    /// already did.
+    pub fn snapshot_epoch(&mut self) -> EpochHeight {
+        self.internal_snapshot_epoch();
+
+        env::epoch_height()
+    }
+
+    pub fn balances_by_epoch(
+        &self,
+        epochs: Option<Vec<EpochHeight>>,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedEpochBalances> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.balances_by_epoch
+            .iter()
+            .filter(|(epoch, _)| epochs.is_none() || epochs.as_ref().unwrap().contains(epoch))
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(epoch, epoch_balances)| WrappedEpochBalances {
+                epoch,
+                total_balance: U128(epoch_balances.total_balance),
+            })
+            .collect()
+    }
+
+    /// An account's principal at the start of each epoch it changed in, oldest first.
+    pub fn account_balances(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<(EpochHeight, U128)> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.account_balances.get(&account_id).map_or_else(Vec::new, |balances| {
+            balances
+                .iter()
+                //skip to the index we specified in the start variable
+                .skip(start as usize)
+                //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+                .take(limit.unwrap_or(50) as usize)
+                .map(|(epoch, balance)| (epoch, U128(balance)))
+                .collect()
+        })
+    }
+
+    /// An account's principal at the start of the given epoch.
+    pub fn balance_at_epoch(&self, account_id: AccountId, epoch: EpochHeight) -> U128 {
+        let principal = self.accounts.get(&account_id).map_or(0, |account| account.principal);
+        let balances = match self.account_balances.get(&account_id) {
+            Some(balances) => balances,
+            None => return U128(principal),
+        };
+
+        // the first change at or after the epoch still saw what the account held at its start
+        let (mut low, mut high) = (0, balances.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if balances.get(middle).unwrap().0 < epoch {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        U128(balances.get(low).map_or(principal, |(_, balance)| balance))
+    }
+}
+
+impl Contract {
+    /// Writes the total for the current epoch the first time it is asked for, later calls in the
+    /// same epoch are no-ops. Only reads the pools, never the accounts.
+    pub(crate) fn internal_snapshot_epoch(&mut self) {
+        let epoch = env::epoch_height();
+        if self.balances_by_epoch.get(&epoch).is_some() {
+            return;
+        }
+
+        let total_balance = self.pools
+            .values()
+            .fold(0, |acc, stake_pool| acc + stake_pool.staked + stake_pool.unstaked + stake_pool.yield_balance);
+
+        self.balances_by_epoch.insert(&epoch, &EpochBalances {
+            total_balance,
+        });
+    }
+
+    /// Records what an account held at the start of this epoch, before its first change in it.
+    /// Later changes in the same epoch write nothing, `extra_storage_in_bytes_per_stake` covers
+    /// the one entry a stake's deposit adds.
+    pub(crate) fn internal_record_balance(&mut self, account_id: &AccountId, principal: Balance) {
+        let epoch = env::epoch_height();
+        let mut balances = self.account_balances.get(account_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::AccountBalanceEpochs { account_id: account_id.clone() }.try_to_vec().unwrap())
+        });
+        if !balances.is_empty() && balances.get(balances.len() - 1).unwrap().0 == epoch {
+            return;
+        }
+
+        balances.push(&(epoch, principal));
+        self.account_balances.insert(account_id, &balances);
+    }
+}
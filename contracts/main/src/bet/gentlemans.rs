@@ -0,0 +1,308 @@
+use crate::*;
+
+use near_sdk::json_types::U128;
+use near_sdk::Timestamp;
+
+/// Terms of a gentleman's stake as given to `new_stake`. Either a counterparty or the sha256 of
+/// an invite code is required, whoever holds the code can take the offer. The code goes out in
+/// the clear when the offer is taken, so the taker commits to it first, see
+/// `commit_gentlemans_invite`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GentlemansTerms {
+    pub counterparty: Option<AccountId>,
+    pub invite_hash: Option<Base64VecU8>,
+    pub expires: Timestamp,
+}
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum OfferStatus {
+    Open,
+    /// A taker's deposit is on its way to the pool.
+    Accepting,
+    Accepted,
+    Expired,
+    Cancelled,
+}
+
+/// A private offer, kept under the stake id of the gentleman's stake that made it.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct GentlemansOffer {
+    pub counterparty: Option<AccountId>,
+    pub invite_hash: Option<Vec<u8>>,
+    pub expires: Timestamp,
+    pub taker: Option<StakeId>,
+    pub status: OfferStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedGentlemansOffer {
+    pub stake_id: StakeId,
+    pub bet_id: BetId,
+    pub position: Position,
+    pub amount: U128,
+    pub odds: Odds,
+    pub counterparty: Option<AccountId>,
+    pub invite_only: bool,
+    pub expires: Timestamp,
+    pub taker: Option<StakeId>,
+    pub status: OfferStatus,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Binds an invite code to the caller ahead of taking the offer, `commitment` is the sha256 of
+    /// the code followed by the caller's account id. Someone reading the code off a pending
+    /// `accept_gentlemans_offer` has no commitment from an earlier block and cannot take the offer
+    /// first. The commitment's storage comes out of the caller's storage deposit until it is used.
+    pub fn commit_gentlemans_invite(&mut self, commitment: Base64VecU8) {
+        if commitment.0.len() != 32 {
+            env::panic_str("ERR_INVALID commitment");
+        }
+        if self.invite_commitments.get(&commitment.0).is_some() {
+            env::panic_str("ERR_EXISTS commitment");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.invite_commitments.insert(&commitment.0, &env::block_height());
+        self.internal_reserve_storage(
+            &env::predecessor_account_id(),
+            env::storage_usage() - initial_storage_usage,
+        );
+    }
+
+    /// Takes the opposite side of a gentleman's offer. Only the named counterparty, or whoever
+    /// gives the invite code it committed to in an earlier block, may take it, the new stake is
+    /// matched against the offer alone and at its odds.
+    #[payable]
+    pub fn accept_gentlemans_offer(
+        &mut self,
+        offer_stake_id: StakeId,
+        stake_id: StakeId,
+        invite_code: Option<String>,
+    ) {
+        self.assert_not_paused(Pausable::NewStake);
+        self.assert_not_paused(Pausable::Matching);
+        self.internal_snapshot_epoch();
+        if env::attached_deposit() < self.config.min_stake {
+            env::panic_str(("ERR_INVALID MIN_STAKE: ".to_owned() + &self.config.min_stake.to_string()).as_str());
+        }
+        if !is_valid_id(stake_id.as_bytes()) {
+            env::panic_str(("ERR_INVALID stake_id : ".to_owned() + &stake_id).as_str());
+        }
+        if self.stakes.get(&stake_id).is_some() {
+            env::panic_str(("ERR_EXISTS stake_id: ".to_owned() + &stake_id).as_str());
+        }
+        let mut offer = self.gentlemans_offers.get(&offer_stake_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST offer_stake_id: ".to_owned() + &offer_stake_id).as_str())
+        });
+        if offer.status != OfferStatus::Open {
+            env::panic_str(("ERR_OFFER_CLOSED status: ".to_owned() + &format!("{:?}", offer.status)).as_str());
+        }
+        if env::block_timestamp() >= offer.expires {
+            env::panic_str(("ERR_EXPIRED offer_stake_id: ".to_owned() + &offer_stake_id).as_str());
+        }
+        let offer_stake = self.stakes.get(&offer_stake_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_PENDING offer_stake_id: ".to_owned() + &offer_stake_id).as_str())
+        });
+        if offer_stake.unmatched == 0 || offer_stake.settled {
+            env::panic_str(("ERR_OFFER_CLOSED offer_stake_id: ".to_owned() + &offer_stake_id).as_str());
+        }
+        let bet = self.bets.get(&offer_stake.bet_id).unwrap();
+        if bet.motion_id.is_some() || self.internal_bet_settling(&offer_stake.bet_id) {
+            env::panic_str(("ERR_BET_CLOSED bet_id: ".to_owned() + &offer_stake.bet_id).as_str());
+        }
+        self.internal_assert_consent(&offer_stake.bet_id, &bet.person, &bet.prediction, bet.end, false);
+
+        let taker = env::predecessor_account_id();
+        let invited = match (&offer.invite_hash, invite_code) {
+            (Some(invite_hash), Some(invite_code)) if &env::sha256(invite_code.as_bytes()) == invite_hash => {
+                self.internal_use_invite_commitment(&invite_code, &taker);
+                true
+            },
+            _ => false,
+        };
+        if taker == offer_stake.staker || !(invited || offer.counterparty.as_ref() == Some(&taker)) {
+            env::panic_str("ERR_NOT_COUNTERPARTY");
+        }
+
+        let taker_storage = self.extra_storage_in_bytes_per_stake;
+        self.internal_reserve_storage(&taker, taker_storage);
+        let amount = env::attached_deposit();
+        let stake = Stake {
+            bet_id: offer_stake.bet_id,
+            position: match offer_stake.position {
+                Position::Back => Position::Lay,
+                Position::Lay => Position::Back,
+            },
+            amount,
+            staker: taker,
+            unmatched: amount,
+            gentlemans: true,
+            epoch: env::epoch_height(),
+            settled: false,
+            pool: self.internal_pool(),
+            odds: offer_stake.odds,
+            seq: 0,
+            accepts: Some(offer_stake_id.clone()),
+            storage_used: taker_storage,
+        };
+
+        // reserved for this taker until the deposit lands, a failed deposit opens it again
+        offer.status = OfferStatus::Accepting;
+        offer.taker = Some(stake_id.clone());
+        self.gentlemans_offers.insert(&offer_stake_id, &offer);
+
+        self.internal_deposit_stake(stake_id, stake, false, false);
+    }
+
+    /// Refunds an offer nobody took before it expired. Anyone may call it, the unmatched stake goes
+    /// through the usual cancellation, `refund_cancelled_stake` finishes the refund.
+    pub fn expire_gentlemans_offer(&mut self, stake_id: StakeId) -> String {
+        self.internal_snapshot_epoch();
+        let mut offer = self.gentlemans_offers.get(&stake_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST stake_id: ".to_owned() + &stake_id).as_str())
+        });
+        match offer.status {
+            OfferStatus::Open => (),
+            OfferStatus::Accepting => env::panic_str(("ERR_PENDING stake_id: ".to_owned() + &stake_id).as_str()),
+            _ => env::panic_str(("ERR_OFFER_CLOSED status: ".to_owned() + &format!("{:?}", offer.status)).as_str()),
+        }
+        if env::block_timestamp() < offer.expires {
+            env::panic_str(("ERR_NOT_EXPIRED expires: ".to_owned() + &offer.expires.to_string()).as_str());
+        }
+        let stake = self.stakes.get(&stake_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_PENDING stake_id: ".to_owned() + &stake_id).as_str())
+        });
+        if self.internal_bet_settling(&stake.bet_id) {
+            env::panic_str(("ERR_SETTLING bet_id: ".to_owned() + &stake.bet_id).as_str());
+        }
+
+        offer.status = OfferStatus::Expired;
+        self.gentlemans_offers.insert(&stake_id, &offer);
+
+        if stake.unmatched > 0 && self.cancelled_stakes.get(&stake_id).is_none() {
+            self.internal_cancel_stake(&stake_id, stake);
+            "expired, refund pending...".to_string()
+        } else {
+            "expired".to_string()
+        }
+    }
+
+    pub fn gentlemans_offers(&self, stake_ids: Vec<StakeId>) -> Vec<WrappedGentlemansOffer> {
+        stake_ids
+            .into_iter()
+            .filter_map(|stake_id| {
+                self.gentlemans_offers.get(&stake_id).map(|offer| self.internal_wrap_offer(stake_id, offer))
+            })
+            .collect()
+    }
+
+    /// Offers naming the given account as counterparty, invite-only offers are never listed.
+    pub fn gentlemans_offers_for_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedGentlemansOffer> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.gentlemans_offers
+            .iter()
+            .filter(|(_, offer)| offer.counterparty.as_ref() == Some(&account_id))
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(stake_id, offer)| self.internal_wrap_offer(stake_id, offer))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Checks the terms of a new gentleman's stake and records its offer.
+    pub(crate) fn internal_create_offer(
+        &mut self,
+        stake_id: &StakeId,
+        terms: GentlemansTerms,
+        end: Timestamp,
+    ) {
+        if terms.counterparty.is_none() && terms.invite_hash.is_none() {
+            env::panic_str("ERR_INVALID gentlemans: counterparty or invite_hash required");
+        }
+        if terms.counterparty.as_ref() == Some(&env::predecessor_account_id()) {
+            env::panic_str("ERR_INVALID counterparty");
+        }
+        if matches!(&terms.invite_hash, Some(invite_hash) if invite_hash.0.len() != 32) {
+            env::panic_str("ERR_INVALID invite_hash");
+        }
+        if terms.expires <= env::block_timestamp() || terms.expires > end {
+            env::panic_str(("ERR_INVALID expires: ".to_owned() + &terms.expires.to_string()).as_str());
+        }
+
+        self.gentlemans_offers.insert(stake_id, &GentlemansOffer {
+            counterparty: terms.counterparty,
+            invite_hash: terms.invite_hash.map(|invite_hash| invite_hash.0),
+            expires: terms.expires,
+            taker: None,
+            status: OfferStatus::Open,
+        });
+    }
+
+    /// Brings an offer up to date once a deposit it depends on settles: a taker landing accepts
+    /// it, a taker failing reopens it and a failed offer stake drops it.
+    pub(crate) fn internal_resolve_offer(&mut self, stake_id: &StakeId, stake: &Stake, deposited: bool) {
+        if let Some(offer_stake_id) = &stake.accepts {
+            if let Some(mut offer) = self.gentlemans_offers.get(offer_stake_id) {
+                if offer.taker.as_ref() == Some(stake_id) {
+                    if deposited {
+                        offer.status = OfferStatus::Accepted;
+                    } else {
+                        offer.status = OfferStatus::Open;
+                        offer.taker = None;
+                    }
+                    self.gentlemans_offers.insert(offer_stake_id, &offer);
+                }
+            }
+        } else if stake.gentlemans && !deposited {
+            self.gentlemans_offers.remove(stake_id);
+        }
+    }
+
+    /// Drops the taker's commitment to an invite code and frees its storage. Panics unless it was
+    /// made in an earlier block than the one revealing the code.
+    fn internal_use_invite_commitment(&mut self, invite_code: &str, taker: &AccountId) {
+        let commitment = env::sha256(&[invite_code.as_bytes(), taker.as_bytes()].concat());
+        let committed: BlockHeight = self.invite_commitments.get(&commitment).unwrap_or_else(|| {
+            env::panic_str("ERR_NOT_COMMITTED invite_code")
+        });
+        if committed >= env::block_height() {
+            env::panic_str(("ERR_PENDING commitment: ".to_owned() + &committed.to_string()).as_str());
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.invite_commitments.remove(&commitment);
+        self.internal_settle_storage(taker, initial_storage_usage - env::storage_usage(), 0);
+    }
+
+    fn internal_wrap_offer(&self, stake_id: StakeId, offer: GentlemansOffer) -> WrappedGentlemansOffer {
+        let stake = self.stakes.get(&stake_id);
+        WrappedGentlemansOffer {
+            bet_id: stake.as_ref().map(|stake| stake.bet_id.clone()).unwrap_or_default(),
+            position: stake.as_ref().map(|stake| stake.position.clone()).unwrap_or(Position::Back),
+            amount: U128(stake.as_ref().map(|stake| stake.amount).unwrap_or(0)),
+            odds: stake.as_ref().map(|stake| stake.odds).unwrap_or(EVEN_ODDS),
+            stake_id,
+            counterparty: offer.counterparty,
+            invite_only: offer.invite_hash.is_some(),
+            expires: offer.expires,
+            taker: offer.taker,
+            status: offer.status,
+        }
+    }
+}
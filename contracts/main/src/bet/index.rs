@@ -0,0 +1,133 @@
+use crate::*;
+
+impl Contract {
+    /// Files a stake under its account and under its bet, by position and by whether it still has
+    /// something to match. Call it after every write that can change `unmatched` or `settled`, a
+    /// settled stake leaves the unmatched sets for good.
+    pub(crate) fn internal_index_stake(&mut self, stake_id: &StakeId, stake: &Stake) {
+        let matched = stake.unmatched == 0 || stake.settled;
+
+        let mut account_stakes = self.stakes_by_account.get(&stake.staker).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::AccountStakes { account_id: stake.staker.clone() }.try_to_vec().unwrap()
+            )
+        });
+        if account_stakes.insert(stake_id) {
+            self.stakes_by_account.insert(&stake.staker, &account_stakes);
+        }
+
+        if let Some(mut stale) = self.internal_bet_index(&stake.position, !matched).get(&stake.bet_id) {
+            if stale.remove(stake_id) {
+                self.internal_bet_index_mut(&stake.position, !matched).insert(&stake.bet_id, &stale);
+            }
+        }
+        let mut current = self.internal_bet_index(&stake.position, matched).get(&stake.bet_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::BetStakes {
+                    bet_id: stake.bet_id.clone(),
+                    position: stake.position.clone(),
+                    matched,
+                }.try_to_vec().unwrap()
+            )
+        });
+        if current.insert(stake_id) {
+            self.internal_bet_index_mut(&stake.position, matched).insert(&stake.bet_id, &current);
+        }
+    }
+
+    /// Drops a stake from every index, emptied sets are removed with it.
+    pub(crate) fn internal_unindex_stake(&mut self, stake_id: &StakeId, stake: &Stake) {
+        if let Some(mut account_stakes) = self.stakes_by_account.get(&stake.staker) {
+            account_stakes.remove(stake_id);
+            if account_stakes.is_empty() {
+                self.stakes_by_account.remove(&stake.staker);
+            } else {
+                self.stakes_by_account.insert(&stake.staker, &account_stakes);
+            }
+        }
+        for matched in [false, true] {
+            if let Some(mut stake_ids) = self.internal_bet_index(&stake.position, matched).get(&stake.bet_id) {
+                stake_ids.remove(stake_id);
+                let index = self.internal_bet_index_mut(&stake.position, matched);
+                if stake_ids.is_empty() {
+                    index.remove(&stake.bet_id);
+                } else {
+                    index.insert(&stake.bet_id, &stake_ids);
+                }
+            }
+        }
+    }
+
+    /// Ids of a bet's stakes, optionally only one position and only the (un)matched ones.
+    pub(crate) fn internal_bet_stake_ids(
+        &self,
+        bet_id: &BetId,
+        position: Option<Position>,
+        matched: Option<bool>,
+    ) -> Vec<StakeId> {
+        let positions = match position {
+            Some(position) => vec![position],
+            None => vec![Position::Back, Position::Lay],
+        };
+        let matched = match matched {
+            Some(matched) => vec![matched],
+            None => vec![false, true],
+        };
+
+        let mut stake_ids: Vec<StakeId> = vec![];
+        for position in positions.iter() {
+            for matched in matched.iter() {
+                if let Some(index) = self.internal_bet_index(position, *matched).get(bet_id) {
+                    stake_ids.extend(index.iter());
+                }
+            }
+        }
+
+        stake_ids
+    }
+
+    /// Whether a bet holds any stake, reading only the lengths of its index sets.
+    pub(crate) fn internal_bet_has_stakes(&self, bet_id: &BetId) -> bool {
+        [Position::Back, Position::Lay].iter().any(|position| {
+            [false, true].iter().any(|matched| {
+                self.internal_bet_index(position, *matched)
+                    .get(bet_id)
+                    .is_some_and(|stake_ids| !stake_ids.is_empty())
+            })
+        })
+    }
+
+    /// A bet's stakes read through the index, see `internal_bet_stake_ids`.
+    pub(crate) fn internal_bet_stakes(
+        &self,
+        bet_id: &BetId,
+        position: Option<Position>,
+        matched: Option<bool>,
+    ) -> Vec<(StakeId, Stake)> {
+        self.internal_bet_stake_ids(bet_id, position, matched)
+            .into_iter()
+            .map(|stake_id| {
+                let stake = self.stakes.get(&stake_id).unwrap();
+                (stake_id, stake)
+            })
+            .collect()
+    }
+
+    pub(crate) fn internal_bet_index(&self, position: &Position, matched: bool) -> &LookupMap<BetId, UnorderedSet<StakeId>> {
+        match (position, matched) {
+            (Position::Back, false) => &self.unmatched_backs_by_bet,
+            (Position::Back, true) => &self.matched_backs_by_bet,
+            (Position::Lay, false) => &self.unmatched_lays_by_bet,
+            (Position::Lay, true) => &self.matched_lays_by_bet,
+        }
+    }
+
+    fn internal_bet_index_mut(&mut self, position: &Position, matched: bool) -> &mut LookupMap<BetId, UnorderedSet<StakeId>> {
+        match (position, matched) {
+            (Position::Back, false) => &mut self.unmatched_backs_by_bet,
+            (Position::Back, true) => &mut self.matched_backs_by_bet,
+            (Position::Lay, false) => &mut self.unmatched_lays_by_bet,
+            (Position::Lay, true) => &mut self.matched_lays_by_bet,
+        }
+    }
+}
@@ -1,124 +1,145 @@
 use crate::*;
 
 use near_sdk::json_types::U128;
-use near_sdk::log; // no-production
 
 #[near_bindgen]
 impl Contract {
-    pub (crate) fn match_stake(
+    /// Puts a bet to its stakers, a keeper is paid once the bet gets its motion.
+    pub (crate) fn internal_request_bet_motion(
         &mut self,
-        stake_id: String
-    ) {
-        let mut new_stake = self.stakes.get(&stake_id).unwrap();
-        if new_stake.gentlemans {
-            return;
-        }
-        /*
-            1. sort highest to lowest
-            2. for each loop if id <= bet_id subtract id from bet_id's unmatched amount, set unmatched amount of id to zero
-        */
-        let mut opposites: Vec<(StakeId, Stake)> = self.stakes.iter()
-            .filter_map(|(stake_id, stake)| {
-                if stake.bet_id == new_stake.bet_id && stake.position != new_stake.position && stake.unmatched > 0 {
-                    Some((stake_id, stake))
-                } else {
-                    None
-                }
-            })
-            .collect();
-        // log!("opposites: {:?}", opposites); // no-production
-        opposites.sort_by(|a, b| a.1.unmatched.cmp(&b.1.unmatched));
-        // log!("opposites (sorted): {:?}", opposites); // no-production
-        for mut stake in opposites.clone() {
-            if stake.1.unmatched <= new_stake.unmatched {
-                new_stake.unmatched = new_stake.unmatched - stake.1.unmatched;
-                stake.1.unmatched = 0;
-                self.stakes.insert(&stake.0, &stake.1);
-                log!("0 stake: {:?}", stake); // no-production
-            } else {
-                stake.1.unmatched = stake.1.unmatched - new_stake.unmatched;
-                self.stakes.insert(&stake.0, &stake.1);
-                new_stake.unmatched = 0;
-        
-                log!("1 stake: {:?}", stake); // no-production
-                break;
-            }
+        bet_id: &BetId,
+        voting_pool: Vec<AccountId>,
+        keeper: Option<AccountId>,
+    ) -> Promise {
+        let bet = self.bets.get(bet_id).unwrap();
+        if voting_pool.is_empty() {
+            env::panic_str("ERR_NO_MATCHING_BETS");
         }
-    }
+        let quorum: u32 = ((voting_pool.len() / 2) + 1) as u32; // improve this
 
-    pub (crate) fn start_bet_settlement(
-        &mut self,
-        bet_ids: Vec<BetId>
-    ) -> Promise {
-        let motions_params: Vec<(Option<String>, String, Option<bool>, Option<u32>, Option<Vec<String>>)> = bet_ids.iter().map(|bet_id| {
-            let voting_pool: Vec<String> = self.stakes.values_as_vector()
-                .iter()
-                .filter(|stake| bet_id == &stake.bet_id.clone())
-                .map(|stake| stake.staker.to_string())
-                .collect();
-            if voting_pool.len() <= 0 {
-                env::panic_str("ERR_NO_MATCHING_BETS");
-            }
-            let quorum: u32 = ((voting_pool.len() / 2) + 1) as u32; // improve this
-            (
+        // motion(&mut self, category: Option<String>, description: String, value: Option<VoteValue>, quorum: Option<u32>, pool: Option<Vec<AccountId>>) -> Motion {
+        ext_voting::ext(self.config.voting_contract.clone())
+            .with_attached_deposit(1)
+            .motions(vec![(
                 Some("bet".to_string()),
-                bet_id.clone(),
+                // voting_callback finds the bet again by the id leading the description
+                format!("{}: {}", bet_id, bet.prediction.motion(&bet.person, bet.end)),
                 None,
                 Some(quorum),
-                Some(voting_pool)
-            )
-        })
-        .collect();
+                Some(voting_pool.iter().map(|staker| staker.to_string()).collect()),
+            )])
+            .then(ext_self::ext(env::current_account_id()).voting_callback(vec![bet_id.clone()], keeper))
+    }
 
-        // motion(&mut self, category: Option<String>, description: String, value: Option<VoteValue>, quorum: Option<u32>, pool: Option<Vec<AccountId>>) -> Motion {
-        ext_voting::ext(VOTING_CONTRACT.parse().unwrap())
-            .with_attached_deposit(1)
-            .motions(motions_params)
-            .then(ext_self::ext(env::current_account_id()).voting_callback(bet_ids.clone()))
+    /// Undoes what `new_stake` wrote before its deposit was rejected. Another stake on the same bet
+    /// may have landed in the meantime, so the bet and person are only dropped while unused.
+    pub(crate) fn internal_revert_stake(
+        &mut self,
+        stake: &Stake,
+        new_bet: bool,
+        new_person: bool,
+    ) {
+        if new_bet && !self.internal_bet_has_stakes(&stake.bet_id) {
+            if let Some(bet) = self.bets.remove(&stake.bet_id) {
+                self.internal_remove_person_bet(&bet.person, &stake.bet_id, new_person);
+            }
+        }
     }
 
-    pub(crate) fn internal_calculate_stake_earning(
-        &self,
+    /// Sends a stake's deposit to its pool, `deposit_and_stake_callback` records it once the pool
+    /// takes it.
+    pub(crate) fn internal_deposit_stake(
+        &mut self,
         stake_id: StakeId,
-    ) -> StakeEarning {        
-        // what happens if/when the stake pool is changed and stakes are spread accross pools?!  Answer: move pool to the stake object.
-        let pool = &self
-            .metadata
+        stake: Stake,
+        new_bet: bool,
+        new_person: bool,
+    ) {
+        let pool = stake.pool.clone();
+        // counted right away so a balance read racing the deposit does not mistake it for yield
+        self.internal_pool_staked(&pool, stake.amount);
+
+        // Call staking contract and insert on return
+        ext_deposit_and_stake::ext(pool)
+            .with_attached_deposit(stake.amount)
+            .deposit_and_stake()
+            .then(ext_self::ext(env::current_account_id()).deposit_and_stake_callback(
+                stake_id,
+                stake,
+                new_bet,
+                new_person,
+            ));
+    }
+
+    /// Takes a stake's unmatched remainder off its bet and starts unstaking it, the refund goes on
+    /// from there through `refund_cancelled_stake`.
+    pub(crate) fn internal_cancel_stake(&mut self, stake_id: &StakeId, stake: Stake) -> &'static str {
+        let cancel_status = if stake.unmatched == stake.amount {
+            "full cancellation pending..."
+        } else {
+            "partial cancellation pending..."
+        };
+
+        let cancelled_stake = CancelledStake {
+            bet_id: stake.bet_id.clone(),
+            amount: stake.unmatched,
+            staker: stake.staker.clone(),
+            epoch: env::epoch_height(),
+            status: CancelStatus::Requested,
+            pending: true,
+            pool: stake.pool.clone(),
+        };
+
+        self.cancelled_stakes.insert(stake_id, &cancelled_stake);
+        EventKind::StakeCancelRequested(vec![StakeCancelRequestedData {
+            stake_id,
+            bet_id: &stake.bet_id,
+            staker: &stake.staker,
+            amount: U128(cancelled_stake.amount),
+            pool: &stake.pool,
+        }])
+        .emit();
+        self.internal_remove_principal(&stake.staker, stake.unmatched);
+        // the yield earned so far stays with the stake, the remainder stops earning
+        self.internal_checkpoint_yield(stake_id, &stake, stake.unmatched);
+        // the unmatched remainder leaves the bet, settlement only pays out what is left on the stake
+        let stake = Stake {
+            amount: stake.amount - stake.unmatched,
+            unmatched: 0,
+            ..stake
+        };
+        self.stakes.insert(stake_id, &stake);
+        self.internal_index_stake(stake_id, &stake);
+
+        ext_unstake::ext(stake.pool).unstake(cancelled_stake.amount.to_string())
+            .then(ext_self::ext(env::current_account_id())
+            .unstake_callback(stake_id.clone()));
+
+        cancel_status
+    }
+
+    pub(crate) fn internal_remove_principal(&mut self, account_id: &AccountId, amount: Balance) {
+        if let Some(mut account) = self.accounts.get(account_id) {
+            self.internal_record_balance(account_id, account.principal);
+            account.principal = account.principal.saturating_sub(amount);
+            self.accounts.insert(account_id, &account);
+        }
+    }
+
+    pub(crate) fn internal_pool(&self) -> AccountId {
+        self.metadata
             .get()
             .unwrap()
             .pool
-            .unwrap_or(DEFAULT_STAKE_POOL.parse().unwrap());
-        let stake = self.stakes.get(&stake_id.clone()).unwrap_or_else(|| {
-            env::panic_str(&("ERR_DOES_NOT_EXIST stake_id: ".to_owned() + &stake_id.clone().to_string()).as_str())
-        });
-    
-        let mut offset = 0;
-        if DEFAULT_STAKE_POOL == "legends.pool.f863973.m0" {
-            offset = 30 * 2; // a month
-        }
-    
-        let epoch = env::epoch_height() + offset;
-        let mut account = self.accounts.get(&stake.staker).unwrap();
-        let mut stake_earning = account.earnings.get(&stake_id.clone()).unwrap();
-        let last_epoch = stake_earning.epochs.1;
-    
-        if last_epoch == epoch {
-            return stake_earning;
-        }
-    
-        let epochs_per_year = 365 * 2;
-        let factor = U256::from(stake.amount) * U256::from(APY.0) / U256::from(APY.1).as_u128();
-        let yield_per_epoch = factor.as_u128() / epochs_per_year;
-        let epochs_staked = epoch - last_epoch;
-    
-        stake_earning = StakeEarning {
-            epochs: (last_epoch, epoch),
-            yield_balance: stake_earning.yield_balance + (yield_per_epoch * epochs_staked as u128),
-        };
-    
-        account.earnings.insert(&stake_id, &stake_earning.clone());
-    
-        stake_earning
+            .unwrap_or_else(|| self.config.default_stake_pool.clone())
+    }
+
+    pub(crate) fn internal_matching_policy(&self) -> MatchingPolicy {
+        self.metadata
+            .get()
+            .unwrap()
+            .matching_policy
+            .unwrap_or_default()
     }
 }
 
@@ -134,15 +155,6 @@ pub(crate) fn assert_deposit(required_cost: Balance) {
     }
 }
 
-pub(crate) fn deduct_storage_cost(storage_used: u64) -> u128 {
-    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
-    let attached_deposit = env::attached_deposit();
-
-    assert_deposit(required_cost);
-
-    attached_deposit - required_cost
-}
-
 pub(crate) fn refund_deposit(storage_used: u64) {
     let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
     let attached_deposit = env::attached_deposit();
@@ -179,18 +191,6 @@ pub(crate) fn assert_one_yocto() {
     }
 }
 
-pub(crate) fn assert_admin(owner_id: &AccountId, admins: &Option<Vec<AccountId>>) {
-    if let Some(admins) = admins {
-        if admins.iter().find(|admin_id| &env::predecessor_account_id() == owner_id || &&env::predecessor_account_id() == admin_id).is_none() {
-            env::panic_str("ERR_NOT_ADMIN");
-        }
-    } else {
-        if &env::predecessor_account_id() != owner_id {
-            env::panic_str("ERR_NOT_ADMIN");
-        }
-    }
-}
-
 pub(crate) fn yton(yocto_amount: Balance) -> String { // no-production
     format!("{:.2}", yocto_amount as f64 / 10u128.pow(24) as f64) // no-production
 } // no-production
\ No newline at end of file
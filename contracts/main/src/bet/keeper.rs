@@ -0,0 +1,66 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+
+#[near_bindgen]
+impl Contract {
+    /// Opens the settlement of ended bets without a motion so it never waits on anyone in
+    /// particular, and works them on as far as one batch goes. Each call looks at the next `limit`
+    /// bets from where the last call stopped, and the caller is paid `Config.keeper_fee` from the
+    /// protocol fees for every bet that gets its motion. Returns how many bets it got to.
+    pub fn process_expired_bets(&mut self, limit: Option<u64>) -> U64 {
+        self.assert_not_paused(Pausable::Settlement);
+        let total = self.bets.len();
+        if total == 0 {
+            return U64(0);
+        }
+        let limit = limit.unwrap_or(MAX_EXPIRED_BETS_PER_CALL).min(MAX_EXPIRED_BETS_PER_CALL).min(total);
+        // removed bets shift the keys, a cursor past the end starts over
+        let start = if self.expired_bets_cursor < total { self.expired_bets_cursor } else { 0 };
+        let now = env::block_timestamp();
+        let keys = self.bets.keys_as_vector();
+
+        let bet_ids: Vec<BetId> = (0..limit)
+            .map(|offset| keys.get((start + offset) % total).unwrap())
+            .filter(|bet_id| {
+                let bet = self.bets.get(bet_id).unwrap();
+                bet.end <= now
+                    && bet.motion_id.is_none()
+                    // one still collecting its stakes picks up where it stopped, and one whose motion
+                    // request failed or timed out asks again, one still in flight is left to land
+                    && self.settlement_cursors.get(bet_id).is_none_or(|cursor| {
+                        cursor.step == SettlementStep::Pending
+                            || (cursor.step == SettlementStep::MotionRequested && !cursor.motion_in_flight())
+                    })
+                    // a withdrawn person's bet is voided through settle_bet instead
+                    && !self.internal_person_withdrawn(&bet.person)
+                    && self.internal_bet_has_stakes(bet_id)
+            })
+            .collect();
+        self.expired_bets_cursor = (start + limit) % total;
+
+        U64(self.internal_settle_bets(bet_ids, Some(env::predecessor_account_id())))
+    }
+
+    pub fn protocol_fees(&self) -> U128 {
+        U128(self.protocol_fees)
+    }
+}
+
+impl Contract {
+    /// Pays a keeper for the bets it put to the vote, as far as the collected fees reach.
+    pub(crate) fn internal_reward_keeper(&mut self, keeper: &AccountId, bet_ids: &[BetId]) {
+        let amount = (self.config.keeper_fee * bet_ids.len() as u128).min(self.protocol_fees);
+        if amount == 0 {
+            return;
+        }
+        self.protocol_fees -= amount;
+        Promise::new(keeper.clone()).transfer(amount);
+        EventKind::KeeperRewarded(vec![KeeperRewardedData {
+            keeper,
+            bet_ids,
+            amount: U128(amount),
+        }])
+        .emit();
+    }
+}
@@ -0,0 +1,153 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+use near_sdk::EpochHeight;
+
+pub type MatchId = u64;
+
+/// One trade between a back and a lay: `amount` of the backer's stake against `liability` of the
+/// layer's, at the lay's or back's resting `odds`.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Match {
+    pub bet_id: BetId,
+    pub back_stake_id: StakeId,
+    pub lay_stake_id: StakeId,
+    pub amount: Balance,
+    pub liability: Balance,
+    pub odds: Odds,
+    pub epoch: EpochHeight,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedMatch {
+    pub match_id: U64,
+    pub bet_id: BetId,
+    pub back_stake_id: StakeId,
+    pub lay_stake_id: StakeId,
+    pub amount: U128,
+    pub liability: U128,
+    pub odds: Odds,
+    pub epoch: EpochHeight,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn matches_for_stake(&self, stake_id: StakeId) -> Vec<WrappedMatch> {
+        self.matches_by_stake
+            .get(&stake_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|match_id| self.internal_wrap_match(match_id))
+            .collect()
+    }
+
+    pub fn matches_for_bet(
+        &self,
+        bet_id: BetId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedMatch> {
+        self.internal_matches(self.matches_by_bet.get(&bet_id), from_index, limit)
+    }
+
+    pub fn matches_for_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedMatch> {
+        self.internal_matches(self.matches_by_account.get(&account_id), from_index, limit)
+    }
+
+    pub fn matches_count(&self) -> U64 {
+        U64::from(self.matches.len())
+    }
+}
+
+impl Contract {
+    /// Appends a match to the ledger and to the indexes of both stakes, the bet and both stakers.
+    pub(crate) fn internal_record_match(
+        &mut self,
+        m: &Match,
+        backer: &AccountId,
+        layer: &AccountId,
+    ) -> MatchId {
+        let match_id = self.matches.len();
+        self.matches.push(m);
+
+        for stake_id in [&m.back_stake_id, &m.lay_stake_id] {
+            let mut match_ids = self.matches_by_stake.get(stake_id).unwrap_or_default();
+            if !match_ids.contains(&match_id) {
+                match_ids.push(match_id);
+                self.matches_by_stake.insert(stake_id, &match_ids);
+            }
+        }
+        let mut bet_matches = self.matches_by_bet.get(&m.bet_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::BetMatches { bet_id: m.bet_id.clone() }.try_to_vec().unwrap())
+        });
+        bet_matches.push(&match_id);
+        self.matches_by_bet.insert(&m.bet_id, &bet_matches);
+        for account_id in if backer == layer { vec![backer] } else { vec![backer, layer] } {
+            let mut account_matches = self.matches_by_account.get(account_id).unwrap_or_else(|| {
+                Vector::new(StorageKey::AccountMatches { account_id: account_id.clone() }.try_to_vec().unwrap())
+            });
+            account_matches.push(&match_id);
+            self.matches_by_account.insert(account_id, &account_matches);
+        }
+
+        match_id
+    }
+
+    /// What a stake won from its counterparties if its side wins: the layers' liability for a
+    /// back, the backers' stakes for a lay.
+    pub(crate) fn internal_counter(&self, stake_id: &StakeId) -> Balance {
+        self.matches_by_stake
+            .get(stake_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|match_id| {
+                let m = self.matches.get(match_id).unwrap();
+                if &m.back_stake_id == stake_id { m.liability } else { m.amount }
+            })
+            .sum()
+    }
+
+    fn internal_matches(
+        &self,
+        match_ids: Option<Vector<MatchId>>,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedMatch> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        match_ids.map_or_else(Vec::new, |match_ids| {
+            match_ids
+                .iter()
+                //skip to the index we specified in the start variable
+                .skip(start as usize)
+                //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+                .take(limit.unwrap_or(50) as usize)
+                .map(|match_id| self.internal_wrap_match(match_id))
+                .collect()
+        })
+    }
+
+    fn internal_wrap_match(&self, match_id: MatchId) -> WrappedMatch {
+        let m = self.matches.get(match_id).unwrap();
+        WrappedMatch {
+            match_id: U64(match_id),
+            bet_id: m.bet_id,
+            back_stake_id: m.back_stake_id,
+            lay_stake_id: m.lay_stake_id,
+            amount: U128(m.amount),
+            liability: U128(m.liability),
+            odds: m.odds,
+            epoch: m.epoch,
+        }
+    }
+}
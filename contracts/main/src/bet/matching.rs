@@ -0,0 +1,234 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+
+/// How an incoming stake is shared among the resting opposite stakes at one price. Whatever the
+/// policy, better odds for the incoming stake are always taken first.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug, Default
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum MatchingPolicy {
+    /// Earliest stake first, by stake epoch and then insertion order.
+    #[default]
+    Fifo,
+    /// Largest unmatched stake first, earliest first among equals.
+    LargestFirst,
+    /// Every stake at the price gets a share in proportion to what it has unmatched.
+    ProRata,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Matches a bet's resting stakes against each other, for stakes that landed while matching
+    /// was paused and never got matched on arrival. Anyone can call it, each call takes the next
+    /// `limit` unmatched stakes from `from_index` and the caller pays for the storage the matches
+    /// take. Returns how many of them matched.
+    #[payable]
+    pub fn rematch(&mut self, bet_id: BetId, from_index: Option<U64>, limit: Option<u64>) -> U64 {
+        self.assert_not_paused(Pausable::Matching);
+        if self.bets.get(&bet_id).is_none() {
+            env::panic_str(("ERR_DOES_NOT_EXIST bet_id: ".to_owned() + &bet_id.to_string()).as_str());
+        }
+        if self.internal_bet_settling(&bet_id) {
+            env::panic_str(("ERR_SETTLING bet_id: ".to_owned() + &bet_id.to_string()).as_str());
+        }
+        let initial_storage_usage = env::storage_usage();
+        let limit = limit.unwrap_or(MAX_REMATCH_PER_CALL).min(MAX_REMATCH_PER_CALL);
+
+        // matching moves stakes between the index sets, so the ids are read before any of it
+        let mut skip = u64::from(from_index.unwrap_or(U64(0)));
+        let mut stake_ids: Vec<StakeId> = vec![];
+        for position in [Position::Back, Position::Lay].iter() {
+            if let Some(unmatched) = self.internal_bet_index(position, false).get(&bet_id) {
+                let len = unmatched.len();
+                let end = len.min(skip + limit - stake_ids.len() as u64);
+                stake_ids.extend((skip.min(len)..end).map(|index| unmatched.as_vector().get(index).unwrap()));
+                skip = skip.saturating_sub(len);
+            }
+        }
+
+        let mut matched = 0;
+        for stake_id in stake_ids {
+            let unmatched = self.stakes.get(&stake_id).unwrap().unmatched;
+            // an earlier stake of this call may have taken it up already
+            if unmatched == 0 {
+                continue;
+            }
+            self.match_stake(stake_id.clone());
+            if self.stakes.get(&stake_id).unwrap().unmatched < unmatched {
+                matched += 1;
+            }
+        }
+
+        internal::refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+
+        U64(matched)
+    }
+}
+
+impl Contract {
+    /// Matches a new stake against resting opposite stakes at compatible odds, best price for the
+    /// new stake first and the bet's matching policy within a price. A back takes lays offering
+    /// at least its odds, a lay takes backs asking at most its odds, and every match trades at
+    /// the resting stake's odds.
+    pub(crate) fn match_stake(
+        &mut self,
+        stake_id: String
+    ) {
+        let mut new_stake = self.stakes.get(&stake_id).unwrap();
+        let policy = self.bets.get(&new_stake.bet_id).unwrap().matching_policy;
+        let opposite_position = match new_stake.position {
+            Position::Back => Position::Lay,
+            Position::Lay => Position::Back,
+        };
+        // a gentleman's stake only ever meets the offer it accepts, an offer waits for its taker
+        let candidates = match (new_stake.gentlemans, &new_stake.accepts) {
+            (false, _) => self.internal_bet_stakes(&new_stake.bet_id, Some(opposite_position), Some(false)),
+            (true, Some(offer_stake_id)) => self.stakes
+                .get(offer_stake_id)
+                .map(|offer_stake| vec![(offer_stake_id.clone(), offer_stake)])
+                .unwrap_or_default(),
+            (true, None) => vec![],
+        };
+        let mut opposites: Vec<(StakeId, Stake)> = candidates
+            .into_iter()
+            .filter(|(_, stake)| {
+                stake.unmatched > 0
+                    && stake.gentlemans == new_stake.gentlemans
+                    && !stake.settled
+                    && match new_stake.position {
+                        Position::Back => stake.odds >= new_stake.odds,
+                        Position::Lay => stake.odds <= new_stake.odds,
+                    }
+            })
+            .collect();
+        opposites.sort_by(|a, b| {
+            match new_stake.position {
+                Position::Back => b.1.odds.cmp(&a.1.odds),
+                Position::Lay => a.1.odds.cmp(&b.1.odds),
+            }
+            .then(a.1.epoch.cmp(&b.1.epoch))
+            .then(a.1.seq.cmp(&b.1.seq))
+        });
+        // log!("opposites (sorted): {:?}", opposites); // no-production
+
+        let mut levels: Vec<Vec<(StakeId, Stake)>> = vec![];
+        for opposite in opposites {
+            match levels.last_mut() {
+                Some(level) if level[0].1.odds == opposite.1.odds => level.push(opposite),
+                _ => levels.push(vec![opposite]),
+            }
+        }
+
+        for mut level in levels {
+            if new_stake.unmatched == 0 {
+                break;
+            }
+            let odds = level[0].1.odds;
+            if policy == MatchingPolicy::LargestFirst {
+                // stable, so equal stakes keep their time order
+                level.sort_by_key(|(_, stake)| std::cmp::Reverse(stake.unmatched));
+            }
+            let allotments = match policy {
+                MatchingPolicy::ProRata => pro_rata_allotments(&new_stake, &level, odds),
+                _ => vec![Balance::MAX; level.len()],
+            };
+
+            for ((opposite_id, mut opposite), allotment) in level.into_iter().zip(allotments) {
+                if new_stake.unmatched == 0 {
+                    break;
+                }
+                self.internal_fill(&stake_id, &mut new_stake, &opposite_id, &mut opposite, odds, allotment);
+            }
+        }
+        self.stakes.insert(&stake_id, &new_stake);
+        self.internal_index_stake(&stake_id, &new_stake);
+    }
+
+    /// Matches up to `allotment` of backer stake between the new stake and one resting opposite,
+    /// writes the opposite and records the match.
+    fn internal_fill(
+        &mut self,
+        stake_id: &StakeId,
+        new_stake: &mut Stake,
+        opposite_id: &StakeId,
+        opposite: &mut Stake,
+        odds: Odds,
+        allotment: Balance,
+    ) {
+        let (back_stake_id, lay_stake_id) = match new_stake.position {
+            Position::Back => (stake_id.clone(), opposite_id.clone()),
+            Position::Lay => (opposite_id.clone(), stake_id.clone()),
+        };
+        let (back, lay) = match new_stake.position {
+            Position::Back => (new_stake, opposite),
+            Position::Lay => (opposite, new_stake),
+        };
+        let (stake, liability) = matched_amounts(std::cmp::min(back.unmatched, allotment), lay.unmatched, odds);
+        if stake == 0 {
+            return;
+        }
+
+        back.unmatched -= stake;
+        lay.unmatched -= liability;
+        let bet_id = back.bet_id.clone();
+        let (backer, layer) = (back.staker.clone(), lay.staker.clone());
+        EventKind::StakeMatched(vec![StakeMatchedData {
+            bet_id: &bet_id,
+            back_stake_id: &back_stake_id,
+            lay_stake_id: &lay_stake_id,
+            backer: &back.staker,
+            layer: &lay.staker,
+            amount: U128(stake),
+            liability: U128(liability),
+            odds,
+        }])
+        .emit();
+        let opposite = if &back_stake_id == opposite_id { back } else { lay };
+        self.stakes.insert(opposite_id, opposite);
+        self.internal_index_stake(opposite_id, opposite);
+        self.internal_record_match(&Match {
+            bet_id,
+            back_stake_id,
+            lay_stake_id,
+            amount: stake,
+            liability,
+            odds,
+            epoch: env::epoch_height(),
+        }, &backer, &layer);
+    }
+}
+
+/// Backer stake each resting stake of a price level gets from the new stake under pro-rata
+/// matching. When the new stake covers the whole level nobody needs to be cut.
+fn pro_rata_allotments(new_stake: &Stake, level: &[(StakeId, Stake)], odds: Odds) -> Vec<Balance> {
+    let capacity = |stake: &Stake| match stake.position {
+        Position::Back => stake.unmatched,
+        Position::Lay => matched_amounts(Balance::MAX, stake.unmatched, odds).0,
+    };
+    let available = U256::from(capacity(new_stake));
+    let capacities: Vec<U256> = level.iter().map(|(_, stake)| U256::from(capacity(stake))).collect();
+    let total = capacities.iter().fold(U256::zero(), |acc, capacity| acc + *capacity);
+
+    if available >= total {
+        return vec![Balance::MAX; level.len()];
+    }
+    capacities
+        .into_iter()
+        .map(|capacity| (available * capacity / total).as_u128())
+        .collect()
+}
+
+/// How much of a back's unmatched stake can be matched against a lay's unmatched liability at the
+/// given odds, and the liability that takes from the lay. Rounds in favour of the lay.
+pub(crate) fn matched_amounts(back_unmatched: Balance, lay_unmatched: Balance, odds: Odds) -> (Balance, Balance) {
+    let margin = U256::from(odds - ODDS_PRECISION);
+    let stake = std::cmp::min(
+        U256::from(back_unmatched),
+        U256::from(lay_unmatched) * U256::from(ODDS_PRECISION) / margin,
+    );
+    let liability = stake * margin / U256::from(ODDS_PRECISION);
+
+    (stake.as_u128(), liability.as_u128())
+}
@@ -1,6 +1,6 @@
 use crate::*;
 
-use near_sdk::json_types::U128;
+use near_sdk::json_types::{U64, U128};
 use near_sdk::{EpochHeight, Timestamp, ext_contract, PromiseResult, serde_json};
 use near_sdk::log; // no-production
 use uint::construct_uint;
@@ -11,18 +11,34 @@ construct_uint! {
 }
 
 pub mod enumerable;
+pub mod consent;
+pub mod epoch;
+pub mod gentlemans;
+pub mod index;
 pub mod internal;
+pub mod keeper;
+pub mod ledger;
+pub mod matching;
+pub mod person;
+pub mod pool;
+pub mod prediction;
+pub mod settlement;
+pub mod takedown;
 
 pub type Person = String;
 pub type BetId = String;
 pub type MotionId = String;
 pub type StakeId = String;
+pub type Odds = u32;
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct StakeEarning {
-    epochs: (EpochHeight, EpochHeight),
-    yield_balance: u128,
+    pub(crate) epochs: (EpochHeight, EpochHeight),
+    /// Yield up to the last checkpoint, see `internal_checkpoint_yield`.
+    pub(crate) yield_balance: u128,
+    /// The pool's yield per share at the last checkpoint, None once the stake stopped earning.
+    pub(crate) yield_per_share: Option<u128>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -42,25 +58,39 @@ pub struct WrappedStakeEarning {
     total_balance: U128,
 }
 
+#[derive(BorshDeserialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "UPPERCASE")]
+pub enum MotionStatus {
+    Open,
+    Closed,
+    Adopted,
+    Rejected,
+}
+
+#[derive(BorshDeserialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MotionLog {
+    pub status: MotionStatus,
+    pub timestamp: Timestamp,
+}
+
 #[derive(BorshDeserialize, Deserialize, Debug, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Motion {
     pub motion_id: MotionId,
     pub category: Option<String>,
     pub description: String,
-    pub status: String,
+    pub status: MotionStatus,
     pub registered: Vec<AccountId>,
-    pub log: Vec<String>,
+    pub log: Vec<MotionLog>,
     pub quorum: Option<u32>,
     pub end: Option<Timestamp>,
 }
 
-#[derive(
-    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug
-)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(crate = "near_sdk::serde")]
-pub enum Prediction {
-    Reduction,
+pub struct JsonMotion {
+    pub motion: Motion,
 }
 
 #[derive(
@@ -80,7 +110,8 @@ pub struct Bet {
     pub prediction: Prediction,
     pub person: String,
     pub end: Timestamp,
-    pub motion_id: Option<String>
+    pub motion_id: Option<String>,
+    pub matching_policy: MatchingPolicy,
 }
 
 #[derive(
@@ -95,6 +126,17 @@ pub struct Stake {
     pub unmatched: Balance,
     pub gentlemans: bool,
     pub epoch: EpochHeight,
+    pub settled: bool,
+    pub pool: AccountId,
+    /// Decimal odds in hundredths: the lowest a back accepts, the highest a lay offers.
+    pub odds: Odds,
+    /// Order the stake was recorded in, breaks ties between stakes of the same epoch.
+    pub seq: u64,
+    /// For a gentleman's stake taking an offer, the stake id of that offer.
+    pub accepts: Option<StakeId>,
+    /// Bytes drawn on the staker's storage deposit, reserved until the deposit lands and given
+    /// back on settlement.
+    pub storage_used: StorageUsage,
 }
 #[derive(
     BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug
@@ -105,7 +147,22 @@ pub struct CancelledStake {
     pub amount: Balance,
     pub staker: AccountId,
     pub epoch: EpochHeight,
-    pub cancelled: bool
+    pub status: CancelStatus,
+    pub pending: bool,
+    pub pool: AccountId,
+}
+/// Lifecycle of a cancelled stake, every step is written by the callback of its pool call so a
+/// retry always picks up from the last confirmed step.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CancelStatus {
+    Requested,
+    Unstaked,
+    Withdrawn,
+    Refunded,
+    Failed,
 }
 #[derive(
     BorshDeserialize, BorshSerialize, Serialize, Deserialize
@@ -116,6 +173,20 @@ pub struct WrappedBet {
     pub prediction: Prediction,
     pub person: String,
     pub end: Timestamp,
+    pub best_back: Option<Odds>,
+    pub best_lay: Option<Odds>,
+    pub matching_policy: MatchingPolicy,
+}
+/// Unmatched liquidity of a bet by odds, in backer stake. `back` lists the odds on offer to a
+/// new back (resting lays), best first, `lay` the odds on offer to a new lay (resting backs).
+#[derive(
+    Serialize, Deserialize
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BetPrices {
+    pub bet_id: BetId,
+    pub back: Vec<(Odds, U128)>,
+    pub lay: Vec<(Odds, U128)>,
 }
 #[derive(
     BorshDeserialize, BorshSerialize, Serialize, Deserialize
@@ -129,6 +200,22 @@ pub struct WrappedStake {
     pub staker: AccountId,
     pub unmatched: U128,
     pub gentlemans: bool,
+    pub pool: AccountId,
+    pub odds: Odds,
+}
+#[derive(
+    Serialize, Deserialize
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedCancelledStake {
+    pub stake_id: StakeId,
+    pub bet_id: BetId,
+    pub amount: U128,
+    pub staker: AccountId,
+    pub epoch: EpochHeight,
+    pub status: CancelStatus,
+    pub pending: bool,
+    pub pool: AccountId,
 }
 
 #[near_bindgen]
@@ -141,10 +228,14 @@ impl Contract {
         position: Position,
         person: String,
         end: Timestamp,
-        gentlemans: Option<bool>,
+        gentlemans: Option<GentlemansTerms>,
+        odds: Option<Odds>,
+        matching_policy: Option<MatchingPolicy>,
     ) {
-        if env::attached_deposit() < MIN_STAKE {
-            env::panic_str(&("ERR_INVALID MIN_STAKE: ".to_owned() + &MIN_STAKE.to_string()).as_str());
+        self.assert_not_paused(Pausable::NewStake);
+        self.internal_snapshot_epoch();
+        if env::attached_deposit() < self.config.min_stake {
+            env::panic_str(&("ERR_INVALID MIN_STAKE: ".to_owned() + &self.config.min_stake.to_string()).as_str());
         }
         if !is_valid_id(&bet_id.as_bytes().to_vec()) {
             env::panic_str(&("ERR_INVALID bet_id: ".to_owned() + &bet_id.to_string()).as_str());
@@ -155,58 +246,95 @@ impl Contract {
         if self.stakes.get(&stake_id).is_some() {
             env::panic_str(&("ERR_EXISTS stake_id: ".to_owned() + &stake_id.to_string()).as_str());
         }
-        let soonest_end = env::block_timestamp() + MIN_DURATION;
+        let odds = odds.unwrap_or(EVEN_ODDS);
+        if !(MIN_ODDS..=MAX_ODDS).contains(&odds) {
+            env::panic_str(("ERR_INVALID odds: ".to_owned() + &odds.to_string()).as_str());
+        }
+        assert_valid_cid(&person);
+        if let Some(bet) = self.bets.get(&bet_id) {
+            if bet.motion_id.is_some() || self.internal_bet_settling(&bet_id) {
+                env::panic_str(("ERR_BET_CLOSED bet_id: ".to_owned() + &bet_id).as_str());
+            }
+            if bet.prediction != prediction {
+                env::panic_str(("ERR_INVALID prediction: ".to_owned() + &format!("{:?}", prediction)).as_str());
+            }
+            if bet.person != person {
+                env::panic_str(("ERR_INVALID person: ".to_owned() + &person).as_str());
+            }
+        }
+        prediction.assert_valid();
+        let new_bet = self.bets.get(&bet_id).is_none();
+        self.internal_assert_consent(&bet_id, &person, &prediction, end, new_bet);
+        let soonest_end = env::block_timestamp() + prediction.min_duration(self.config.min_duration);
         if end <= soonest_end {
             env::panic_str(&("ERR_INVALID end: ".to_owned() + &end.to_string().as_str() + " <= " + &soonest_end.to_string()));
         }
 
-        let pool = &self
-            .metadata
-            .get()
-            .unwrap()
-            .pool
-            .unwrap_or(DEFAULT_STAKE_POOL.parse().unwrap());
-        let mut amount = internal::deduct_storage_cost(self.extra_storage_in_bytes_per_stake);
+        let pool = self.internal_pool();
+        let initial_storage_usage = env::storage_usage();
+        let mut new_person = false;
 
-        self.bets.get(&bet_id).unwrap_or({
-            let bet = Bet {
+        if new_bet {
+            new_person = self.internal_add_person_bet(&person, &bet_id);
+            self.bets.insert(&bet_id, &Bet {
                 prediction,
                 person: person.clone(),
                 end,
                 motion_id: None,
-            };
-            self.bets.insert(&bet_id, &bet);
-            amount = internal::deduct_storage_cost(self.extra_storage_in_bytes_per_bet);
-
-            bet
-        });
+                // only the stake opening a bet picks its policy, later stakes play by it
+                matching_policy: matching_policy.unwrap_or_else(|| self.internal_matching_policy()),
+            });
+        }
+        let is_gentlemans = gentlemans.is_some();
+        if let Some(terms) = gentlemans {
+            self.internal_create_offer(&stake_id, terms, end);
+        }
+        // what was written here plus room for what the deposit callback writes
+        let storage_reserved = env::storage_usage() - initial_storage_usage + self.extra_storage_in_bytes_per_stake;
+        self.internal_reserve_storage(&env::predecessor_account_id(), storage_reserved);
+        let amount = env::attached_deposit();
 
         let stake = Stake {
             bet_id,
             position,
-            amount: amount.clone(),
+            amount,
             staker: env::predecessor_account_id(),
             unmatched: amount,
-            gentlemans: gentlemans.unwrap_or(false),
+            gentlemans: is_gentlemans,
             epoch: env::epoch_height(),
+            settled: false,
+            pool: pool.clone(),
+            odds,
+            seq: 0,
+            accepts: None,
+            storage_used: storage_reserved,
         };
 
-        self.persons.insert(&person);
-
-        // Call staking contract and insert on return
-        ext_deposit_and_stake::ext(pool.clone())
-            .with_attached_deposit(amount.clone())
-            .deposit_and_stake()
-            .then(ext_self::ext(env::current_account_id()).deposit_and_stake_callback(stake_id.clone(), stake));
+        self.internal_deposit_stake(stake_id, stake, new_bet, new_person);
     }
 
+    /// Asks every pool the given stakes sit in for its balance, the growth is attributed to the
+    /// stakes once the pools answer. Returns the earnings recorded so far.
     pub fn update_stake_earnings(
-        &self,
+        &mut self,
         stake_ids: Vec<StakeId>
     ) -> Vec<StakeEarning> {
+        let mut pools: Vec<AccountId> = vec![];
+
         stake_ids
             .into_iter()
-            .map(|stake_id| self.internal_calculate_stake_earning(stake_id))
+            .filter_map(|stake_id| {
+                let stake = self.stakes.get(&stake_id).unwrap_or_else(|| {
+                    env::panic_str(("ERR_DOES_NOT_EXIST stake_id: ".to_owned() + &stake_id).as_str())
+                });
+                if !pools.contains(&stake.pool) {
+                    self.update_pool_yield(stake.pool.clone());
+                    pools.push(stake.pool);
+                }
+
+                // settled stakes paid their earnings out and no longer keep them
+                self.accounts.get(&stake.staker).unwrap().earnings.get(&stake_id)
+            })
             .collect()
     }
 
@@ -215,12 +343,8 @@ impl Contract {
         bet_id: BetId,
         stake_id: String,
     ) -> String {
-        let pool = &self
-            .metadata
-            .get()
-            .unwrap()
-            .pool
-            .unwrap_or(DEFAULT_STAKE_POOL.parse().unwrap());
+        self.assert_not_paused(Pausable::Cancellation);
+        self.internal_snapshot_epoch();
         let initial_storage_usage = env::storage_usage();
         let stake = self.stakes.get(&stake_id).unwrap_or_else(|| {
             env::panic_str(&("ERR_DOES_NOT_EXIST stake_id: ".to_owned() + &stake_id.to_string()).as_str())
@@ -230,6 +354,9 @@ impl Contract {
         if self.bets.get(&bet_id).is_none() {
             env::panic_str(&("ERR_DOES_NOT_EXIST bet_id: ".to_owned() + &bet_id.to_string()).as_str());
         }
+        if self.internal_bet_settling(&bet_id) {
+            env::panic_str(&("ERR_SETTLING bet_id: ".to_owned() + &bet_id.to_string()).as_str());
+        }
         if stake.staker != env::predecessor_account_id() {
             env::panic_str("ERR_NOT_AUTHORIZED");
         }
@@ -237,26 +364,18 @@ impl Contract {
             env::panic_str(&("ERR_ALREADY_CANCELLED stake_id: ".to_owned() + &stake_id.to_string()).as_str())
         }
 
-        if stake.unmatched > 0 {
-            if stake.unmatched == stake.amount {
-                cancel_status = "full cancellation pending...";
-            } else {
-                cancel_status = "partial cancellation pending...";
+        if let Some(mut offer) = self.gentlemans_offers.get(&stake_id) {
+            if offer.status == OfferStatus::Accepting {
+                env::panic_str(("ERR_PENDING stake_id: ".to_owned() + &stake_id).as_str());
+            }
+            if offer.status == OfferStatus::Open {
+                offer.status = OfferStatus::Cancelled;
+                self.gentlemans_offers.insert(&stake_id, &offer);
             }
+        }
 
-            let cancelled_stake = CancelledStake {
-                bet_id: bet_id.clone(),
-                amount: stake.unmatched.clone(),
-                staker: env::predecessor_account_id(),
-                epoch: env::epoch_height(),
-                cancelled: false,
-            };
-
-            self.cancelled_stakes.insert(&stake_id, &cancelled_stake.clone());
-            
-            ext_unstake::ext(pool.clone()).unstake(stake.unmatched.to_string())
-                .then(ext_self::ext(env::current_account_id())
-                .unstake_callback(stake_id));
+        if stake.unmatched > 0 {
+            cancel_status = self.internal_cancel_stake(&stake_id, stake);
         }
 
         internal::refund_deposit(env::storage_usage() - initial_storage_usage);
@@ -264,30 +383,63 @@ impl Contract {
         cancel_status.to_string()
     }
 
+    /// Moves a cancelled stake one step further along Requested -> Unstaked -> Withdrawn -> Refunded,
+    /// retrying whichever pool call or transfer last failed.
     pub fn refund_cancelled_stake(&mut self,
         stake_id: String,
-    ) {
+    ) -> String {
+        self.assert_not_paused(Pausable::Refunds);
         let cancelled_stake = self.cancelled_stakes.get(&stake_id).unwrap_or_else(|| {
             env::panic_str(&("ERR_DOES_NOT_EXIST stake_id: ".to_owned() + &stake_id.to_string()).as_str())
         });
-        if cancelled_stake.clone().cancelled {
-            env::panic_str("ERR_CANCELLED");
+        if cancelled_stake.pending {
+            env::panic_str(("ERR_PENDING stake_id: ".to_owned() + &stake_id).as_str());
         }
-        let epochs_elapsed = env::epoch_height() - cancelled_stake.clone().epoch;
-        if epochs_elapsed < REFUND_DELAY_EPOCHS.into() {
-            env::panic_str(&("ERR_LOCKED epochs_elapsed: ".to_owned() + &epochs_elapsed.to_string()).as_str());
+        let pool = cancelled_stake.pool.clone();
+
+        match cancelled_stake.status {
+            CancelStatus::Requested | CancelStatus::Failed => {
+                ext_unstake::ext(pool).unstake(cancelled_stake.amount.to_string())
+                    .then(ext_self::ext(env::current_account_id())
+                    .unstake_callback(stake_id.clone()));
+            },
+            CancelStatus::Unstaked => {
+                let epochs_elapsed = env::epoch_height() - cancelled_stake.epoch;
+                if epochs_elapsed < self.config.refund_delay_epochs.into() {
+                    env::panic_str(("ERR_LOCKED epochs_elapsed: ".to_owned() + &epochs_elapsed.to_string()).as_str());
+                }
+                ext_withdraw::ext(pool).withdraw(cancelled_stake.amount.to_string())
+                    .then(ext_self::ext(env::current_account_id())
+                    .withdraw_cancelled_stake_callback(stake_id.clone()));
+            },
+            CancelStatus::Withdrawn => {
+                Promise::new(cancelled_stake.staker.clone()).transfer(cancelled_stake.amount)
+                    .then(ext_self::ext(env::current_account_id())
+                    .refund_cancelled_stake_callback(stake_id.clone()));
+            },
+            CancelStatus::Refunded => env::panic_str("ERR_REFUNDED"),
         }
-        Promise::new(cancelled_stake.clone().staker).transfer(cancelled_stake.clone().amount)
-            .then(ext_self::ext(env::current_account_id())
-            .refund_cancelled_stake_callback(cancelled_stake));
+
+        self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+            pending: true,
+            ..cancelled_stake.clone()
+        });
+
+        format!("{:?} -> pending...", cancelled_stake.status)
     }
 
     #[payable]
     pub fn change_stake_pool(&mut self, pool: AccountId) -> ContractMetadata {
-        assert_admin(&self.owner_id, &self.metadata.get().unwrap().admins);
+        self.assert_role(Role::PoolManager);
         assert_one_yocto();
 
         let metadata = self.metadata.get().unwrap();
+        EventKind::PoolChanged(vec![PoolChangedData {
+            old_pool: metadata.pool.as_ref(),
+            new_pool: &pool,
+            changed_by: &env::predecessor_account_id(),
+        }])
+        .emit();
         let updated_metadata = ContractMetadata {
             pool: Some(pool),
             ..metadata
@@ -298,14 +450,47 @@ impl Contract {
         updated_metadata
     }
 
+    #[payable]
+    pub fn change_matching_policy(&mut self, matching_policy: MatchingPolicy) -> ContractMetadata {
+        self.assert_role(Role::ConfigAdmin);
+        assert_one_yocto();
+
+        let metadata = self.metadata.get().unwrap();
+        let updated_metadata = ContractMetadata {
+            matching_policy: Some(matching_policy),
+            ..metadata
+        };
+
+        self.metadata.replace(&updated_metadata);
+
+        updated_metadata
+    }
+
+    /// Replaces the admin list, every listed account holds `ADMIN_ROLES` and accounts dropped from
+    /// the list lose them.
     #[payable]
     pub fn change_admin_users(&mut self, admins: Vec<AccountId>) -> ContractMetadata {
         assert_one_yocto();
+        self.assert_role(Role::Owner);
 
         let metadata = self.metadata.get().unwrap();
+        for admin_id in metadata.admins.iter().flatten().filter(|admin_id| !admins.contains(admin_id)) {
+            for role in ADMIN_ROLES {
+                self.internal_revoke_role(admin_id, role);
+            }
+        }
+        for admin_id in admins.iter() {
+            for role in ADMIN_ROLES {
+                self.internal_grant_role(admin_id, role);
+            }
+        }
+        EventKind::AdminChanged(vec![AdminChangedData {
+            old_admins: metadata.admins.as_deref().unwrap_or_default(),
+            new_admins: &admins,
+            changed_by: &env::predecessor_account_id(),
+        }])
+        .emit();
 
-        assert_admin(&self.owner_id, &self.metadata.get().unwrap().admins);
-        
         let updated_metadata = ContractMetadata {
             admins: Some(admins),
             ..metadata
@@ -316,55 +501,127 @@ impl Contract {
         updated_metadata
     }
 
+    /// Opens the settlement of the given ended bets and works it on, one shared batch across all
+    /// of them. Returns how many bets it got to.
     #[payable]
-    pub fn check_bets(&mut self, bet_ids: Option<Vec<MotionId>>) -> Promise {
-        // a valid user is required other than the contract itself
+    pub fn check_bets(&mut self, bet_ids: Option<Vec<MotionId>>) -> U64 {
+        self.assert_not_paused(Pausable::Settlement);
+        // a keeper is required other than the contract itself
         if env::predecessor_account_id() != env::current_account_id() {
             assert_one_yocto();
+            self.assert_role(Role::Keeper);
         }
         let now = env::block_timestamp() + env::block_height();
-        let filtered_bet_ids = self.bets
-            .iter()
-            .filter(|(bet_id, bet)| bet_ids.as_ref().unwrap_or(&vec![]).contains(bet_id) && bet.end > now && bet.motion_id.is_none())
-            .map(|(bet_id, _)| bet_id)
+        let filtered_bet_ids: Vec<BetId> = bet_ids
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|bet_id| {
+                self.bets.get(bet_id).is_some_and(|bet| bet.end <= now && bet.motion_id.is_none())
+            })
             .collect();
 
-        self.start_bet_settlement(filtered_bet_ids)
+        U64(self.internal_settle_bets(filtered_bet_ids, None))
     }
 
-    pub fn deposit_and_stake_callback(&mut self, stake_id: String, stake: Stake) -> String {
+    pub fn deposit_and_stake_callback(&mut self,
+        stake_id: String,
+        stake: Stake,
+        new_bet: bool,
+        new_person: bool,
+    ) -> String {
         assert_callback();
       
-        let mut offset = 0;
-        if DEFAULT_STAKE_POOL == "legends.pool.f863973.m0" {
-            offset = 30 * 2; // a month
-        }
         // handle the result from the cross contract call this method is a callback for
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Failed => {
+                self.internal_pool_deposit_reverted(&stake.pool, stake.amount);
+                self.internal_resolve_offer(&stake_id, &stake, false);
+                self.internal_revert_stake(&stake, new_bet, new_person);
+                // nothing reached the pool, the storage reserved for the stake is free again
+                self.internal_settle_storage(&stake.staker, stake.storage_used, 0);
+                Promise::new(stake.staker.clone()).transfer(stake.amount);
+                EventKind::StakeFailed(vec![StakeFailedData {
+                    stake_id: &stake_id,
+                    bet_id: &stake.bet_id,
+                    staker: &stake.staker,
+                    amount: U128(stake.amount),
+                }])
+                .emit();
+                "oops!".to_string()
+            },
             PromiseResult::Successful(_result) => {
-                // maybe we should be saving some unique id from the staking pool transaction for fast lookups later during calculations, etc... also to double check our own time based APY calculations. In which case the stake object should be updated to hold said id.
+                let initial_storage_usage = env::storage_usage();
+                let written_before = stake.storage_used - self.extra_storage_in_bytes_per_stake;
+                let stake = Stake {
+                    seq: self.next_stake_seq,
+                    ..stake
+                };
+                self.next_stake_seq += 1;
                 self.stakes.insert(&stake_id.clone(), &stake);
-                let epoch = env::epoch_height() + offset;
+                self.internal_index_stake(&stake_id, &stake);
+                self.internal_resolve_offer(&stake_id, &stake, true);
+                let epoch = env::epoch_height();
                 let account = self.accounts.get(&stake.staker);
                 let earning = StakeEarning {
                     epochs: (epoch.clone(), epoch),
-                    yield_balance: 0
+                    yield_balance: 0,
+                    yield_per_share: Some(self.internal_pool_joined(&stake.pool, stake.amount)),
                 };
 
+                self.internal_record_balance(&stake.staker, account.as_ref().map_or(0, |account| account.principal));
                 if let Some(mut account) = account {
                     account.earnings.insert(&stake_id, &earning);
+                    account.principal += stake.amount;
                     self.accounts.insert(&stake.staker, &account);
                 } else {
-                    let mut earnings = UnorderedMap::new(earning.try_to_vec().unwrap());
+                    let mut earnings = UnorderedMap::new(
+                        StorageKey::Earnings { account_id: stake.staker.clone() }.try_to_vec().unwrap()
+                    );
                     earnings.insert(&stake_id, &earning);
                     self.accounts.insert(&stake.staker, &Account {
-                        earnings
+                        earnings,
+                        principal: stake.amount,
                     });
                 }
 
-                self.match_stake(stake_id);
+                if new_bet {
+                    let bet = self.bets.get(&stake.bet_id).unwrap();
+                    EventKind::BetCreated(vec![BetCreatedData {
+                        bet_id: &stake.bet_id,
+                        person: &bet.person,
+                        prediction: &bet.prediction,
+                        end: bet.end,
+                        matching_policy: &bet.matching_policy,
+                        creator: &stake.staker,
+                    }])
+                    .emit();
+                }
+                EventKind::StakeCreated(vec![StakeCreatedData {
+                    stake_id: &stake_id,
+                    bet_id: &stake.bet_id,
+                    staker: &stake.staker,
+                    position: &stake.position,
+                    amount: U128(stake.amount),
+                    odds: stake.odds,
+                    gentlemans: stake.gentlemans,
+                    pool: &stake.pool,
+                }])
+                .emit();
+                // a paused matching leaves the stake resting until `rematch` picks it up. A settling
+                // bet is not reshuffled, the stake joins its settlement as it is
+                if self.internal_bet_settling(&stake.bet_id) {
+                    self.internal_settlement_stake_landed(&stake_id, &stake);
+                } else if !self.internal_paused(Pausable::Matching) {
+                    self.match_stake(stake_id.clone());
+                }
+
+                // the reservation becomes what was actually written, the stake keeps it until settled
+                let storage_used = written_before + (env::storage_usage().saturating_sub(initial_storage_usage));
+                let mut stake = self.stakes.get(&stake_id).unwrap();
+                self.internal_settle_storage(&stake.staker, stake.storage_used, storage_used);
+                stake.storage_used = storage_used;
+                self.stakes.insert(&stake_id, &stake);
                 "ok".to_string()
             },
         }
@@ -372,49 +629,123 @@ impl Contract {
 
     pub fn unstake_callback(&mut self, stake_id: String) -> String {
         assert_callback();
-      
+
+        let cancelled_stake = self.cancelled_stakes.get(&stake_id).unwrap();
         // handle the result from the cross contract call this method is a callback for
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Failed => {
-                self.cancelled_stakes.remove(&stake_id);
+                self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+                    status: CancelStatus::Failed,
+                    pending: false,
+                    ..cancelled_stake
+                });
                 "oops!".to_string()
             },
             PromiseResult::Successful(_result) => {
+                self.internal_pool_unstaked(&cancelled_stake.pool, cancelled_stake.amount, 0);
+                // the refund delay counts from the epoch the pool actually unstaked
+                self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+                    status: CancelStatus::Unstaked,
+                    epoch: env::epoch_height(),
+                    pending: false,
+                    ..cancelled_stake
+                });
                 "ok".to_string()
             },
         }
     }
 
-    pub fn refund_cancelled_stake_callback(&self, cancelled_stake: &mut CancelledStake) -> String {
+    pub fn withdraw_cancelled_stake_callback(&mut self, stake_id: String) -> String {
         assert_callback();
-      
+
+        let cancelled_stake = self.cancelled_stakes.get(&stake_id).unwrap();
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => {
+                self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+                    pending: false,
+                    ..cancelled_stake
+                });
+                "oops!".to_string()
+            },
+            PromiseResult::Successful(_result) => {
+                self.internal_pool_withdrawn(&cancelled_stake.pool, cancelled_stake.amount);
+                self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+                    status: CancelStatus::Withdrawn,
+                    pending: true,
+                    ..cancelled_stake.clone()
+                });
+                Promise::new(cancelled_stake.staker).transfer(cancelled_stake.amount)
+                    .then(ext_self::ext(env::current_account_id())
+                    .refund_cancelled_stake_callback(stake_id));
+                "ok".to_string()
+            },
+        }
+    }
+
+    pub fn refund_cancelled_stake_callback(&mut self, stake_id: String) -> String {
+        assert_callback();
+
+        let cancelled_stake = self.cancelled_stakes.get(&stake_id).unwrap();
         // handle the result from the cross contract call this method is a callback for
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Failed => {
+                self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+                    pending: false,
+                    ..cancelled_stake
+                });
+                "oops!".to_string()
+            },
             PromiseResult::Successful(_result) => {
-                cancelled_stake.cancelled = true;
+                EventKind::StakeRefunded(vec![StakeRefundedData {
+                    stake_id: &stake_id,
+                    staker: &cancelled_stake.staker,
+                    amount: U128(cancelled_stake.amount),
+                }])
+                .emit();
+                self.cancelled_stakes.insert(&stake_id, &CancelledStake {
+                    status: CancelStatus::Refunded,
+                    pending: false,
+                    ..cancelled_stake
+                });
                 "ok".to_string()
             },
         }
     }
 
-    pub fn voting_callback(&mut self, bet_ids: &Vec<BetId>) -> Option<Vec<Option<(MotionId, BetId)>>> {
+    pub fn voting_callback(&mut self, bet_ids: &Vec<BetId>, keeper: Option<AccountId>) -> Option<Vec<Option<(MotionId, BetId)>>> {
         assert_callback();
       
         // handle the result from the cross contract call this method is a callback for
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Failed => None,
+            PromiseResult::Failed => {
+                // nothing was opened, the next settle_bet call asks again right away
+                for bet_id in bet_ids {
+                    if let Some(mut cursor) = self.settlement_cursors.get(bet_id) {
+                        cursor.motion_requested_at = None;
+                        self.settlement_cursors.insert(bet_id, &cursor);
+                    }
+                }
+                None
+            },
             PromiseResult::Successful(result) => {
                 let motions: Vec<Motion> = serde_json::from_slice(&result).unwrap();
-                Some(motions.into_iter().map(|motion| {
+                let mut rewarded: Vec<BetId> = vec![];
+                let motion_bets = motions.into_iter().map(|motion| {
                     let bet_id = bet_ids.iter().find(|bet_id| motion.description.contains(bet_id.as_str()));
 
                     if let Some(bet_id) = bet_id {
                         let bet = self.bets.get(&bet_id).unwrap();
-                    
+                        // a racing call already put it to the vote, its motion stands
+                        if bet.motion_id.is_some() {
+                            return None;
+                        }
+                        rewarded.push(bet_id.to_owned());
+
                         self.bets.remove(&bet_id);
                         self.bets.insert(&bet_id, &Bet {
                             motion_id: Some(motion.clone().motion_id),
@@ -426,7 +757,11 @@ impl Contract {
                     }
                 })
                 .filter(|m| m.is_some())
-                .collect())
+                .collect();
+                if let Some(keeper) = keeper {
+                    self.internal_reward_keeper(&keeper, &rewarded);
+                }
+                Some(motion_bets)
             },
         }
     }
@@ -435,7 +770,7 @@ impl Contract {
 
 #[ext_contract(ext_get_account_total_balance)]
 pub trait GetAccountTotalBalance {
-    fn get_account_total_balance(&mut self);
+    fn get_account_total_balance(&self, account_id: AccountId) -> U128;
 }
 
 #[ext_contract(ext_deposit_and_stake)]
@@ -448,6 +783,11 @@ pub trait Unstake {
     fn unstake(&mut self, amount: String);
 }
 
+#[ext_contract(ext_withdraw)]
+pub trait Withdraw {
+    fn withdraw(&mut self, amount: String);
+}
+
 #[ext_contract(ext_voting)]
 pub trait CreateMotion {
     fn motions(&mut self, params: Vec<(
@@ -457,12 +797,25 @@ pub trait CreateMotion {
         Option<u32>,
         Option<Vec<String>>
     )>);
+    fn view_motions(&self, from_index: Option<U128>, limit: Option<u64>, motion_ids: Option<Vec<MotionId>>, category: Option<String>);
 }
 
 #[ext_contract(ext_self)]
 trait ExtSelf {
-    fn deposit_and_stake_callback(&self, stake_id: String, stake: Stake) -> String;
+    fn deposit_and_stake_callback(&self, stake_id: String, stake: Stake, new_bet: bool, new_person: bool) -> String;
     fn unstake_callback(&self, stake_id: String) -> String;
-    fn refund_cancelled_stake_callback(&self, cancelled_stake: CancelledStake) -> String;
-    fn voting_callback(&self, bet_ids: Vec<String>) -> Vec<Option<(String, String)>>;
+    fn withdraw_cancelled_stake_callback(&self, stake_id: String) -> String;
+    fn refund_cancelled_stake_callback(&self, stake_id: String) -> String;
+    fn voting_callback(&self, bet_ids: Vec<String>, keeper: Option<AccountId>) -> Vec<Option<(String, String)>>;
+    fn settle_motion_callback(&self, bet_id: BetId) -> String;
+    fn pool_balance_callback(&self, pool: AccountId) -> String;
+    fn settle_unstake_callback(&self, bet_id: BetId, pool: AccountId) -> String;
+    fn settle_withdraw_callback(&self, bet_id: BetId, pool: AccountId) -> String;
+    fn takedown_motion_callback(&self, takedown_id: TakedownId) -> String;
+    fn takedown_resolve_callback(&self, takedown_id: TakedownId) -> String;
+    fn upgrade_motion_callback(&self, hash: String) -> String;
+    fn upgrade_resolve_callback(&self, hash: String) -> String;
+    fn upgrade_deployed_callback(&self, hash: String) -> String;
+    fn unpause_motion_callback(&self, method: Pausable) -> String;
+    fn unpause_resolve_callback(&self, method: Pausable, motion_id: MotionId) -> String;
 }
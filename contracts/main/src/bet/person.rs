@@ -0,0 +1,253 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+use near_sdk::Timestamp;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PersonStatus {
+    Active,
+    /// Kept for the bets already on it, no new bet can be opened.
+    Inactive,
+    /// The person withdrew consent, no stake is taken and open bets are voided.
+    OptedOut,
+    /// Removed by a takedown, same as an opt-out.
+    TakenDown,
+}
+
+/// A person bets are made on, kept under the IPFS CID of their reference media.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PersonRecord {
+    pub alias: Option<String>,
+    /// sha256 of the reference media, so a re-pinned CID can be told apart from a different one.
+    pub reference_hash: Option<Base64VecU8>,
+    pub created: Timestamp,
+    pub creator: AccountId,
+    pub status: PersonStatus,
+    pub bet_count: u64,
+    /// Account the person controls, it approves new bets on them and can opt out.
+    pub account_id: Option<AccountId>,
+    /// Set inactive by a moderator, only a moderator makes it active again.
+    pub moderated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedPerson {
+    pub person: Person,
+    pub alias: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+    pub created: Timestamp,
+    pub creator: AccountId,
+    pub status: PersonStatus,
+    pub bet_count: U64,
+    pub account_id: Option<AccountId>,
+    pub moderated: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn add_person(
+        &mut self,
+        person: Person,
+        alias: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+    ) -> WrappedPerson {
+        let initial_storage_usage = env::storage_usage();
+        assert_valid_cid(&person);
+        assert_valid_person_details(&alias, &reference_hash);
+        if self.persons.get(&person).is_some() {
+            env::panic_str(("ERR_EXISTS person: ".to_owned() + &person).as_str());
+        }
+
+        let record = PersonRecord {
+            alias,
+            reference_hash,
+            created: env::block_timestamp(),
+            creator: env::predecessor_account_id(),
+            status: PersonStatus::Active,
+            bet_count: 0,
+            account_id: None,
+            moderated: false,
+        };
+        self.persons.insert(&person, &record);
+
+        internal::refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        wrap_person(person, record)
+    }
+
+    /// Lets the creator of a person, or a moderator, change its alias, reference hash or status. A
+    /// person a moderator set inactive stays so until a moderator lifts it. A longer record is
+    /// paid for by the attached deposit.
+    #[payable]
+    pub fn update_person(
+        &mut self,
+        person: Person,
+        alias: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+        status: Option<PersonStatus>,
+    ) -> WrappedPerson {
+        let initial_storage_usage = env::storage_usage();
+        let record = self.persons.get(&person).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST person: ".to_owned() + &person).as_str())
+        });
+        let moderating = record.creator != env::predecessor_account_id();
+        if moderating || (record.moderated && status.is_some()) {
+            self.assert_role(Role::Moderator);
+        }
+        assert_valid_person_details(&alias, &reference_hash);
+        if matches!(status, Some(PersonStatus::OptedOut) | Some(PersonStatus::TakenDown))
+            || matches!(record.status, PersonStatus::OptedOut | PersonStatus::TakenDown) {
+            // opt-outs and takedowns have their own paths and are not undone from here
+            env::panic_str(("ERR_INVALID status: ".to_owned() + &format!("{:?}", record.status)).as_str());
+        }
+
+        let moderated = match &status {
+            Some(PersonStatus::Inactive) => moderating || record.moderated,
+            Some(_) => false,
+            None => record.moderated,
+        };
+        let record = PersonRecord {
+            alias: alias.or(record.alias),
+            reference_hash: reference_hash.or(record.reference_hash),
+            status: status.unwrap_or(record.status),
+            moderated,
+            ..record
+        };
+        self.persons.insert(&person, &record);
+
+        internal::refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+
+        wrap_person(person, record)
+    }
+
+    pub fn person(&self, person: Person) -> Option<WrappedPerson> {
+        self.persons.get(&person).map(|record| wrap_person(person, record))
+    }
+
+    pub fn person_records(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedPerson> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.persons
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(person, record)| wrap_person(person, record))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Counts a new bet on a person, recording the person first if nobody has yet. Returns whether
+    /// the record was created here.
+    pub(crate) fn internal_add_person_bet(&mut self, person: &Person, bet_id: &BetId) -> bool {
+        let (mut record, new_person) = match self.persons.get(person) {
+            Some(record) => (record, false),
+            None => (PersonRecord {
+                alias: None,
+                reference_hash: None,
+                created: env::block_timestamp(),
+                creator: env::predecessor_account_id(),
+                status: PersonStatus::Active,
+                bet_count: 0,
+                account_id: None,
+                moderated: false,
+            }, true),
+        };
+        if record.status != PersonStatus::Active {
+            env::panic_str(("ERR_PERSON_INACTIVE person: ".to_owned() + person).as_str());
+        }
+        record.bet_count += 1;
+        self.persons.insert(person, &record);
+        self.internal_index_person_bet(person, bet_id);
+
+        new_person
+    }
+
+    /// Takes back `internal_add_person_bet`, the record goes as well if that call created it and no
+    /// other bet counts on it since.
+    pub(crate) fn internal_remove_person_bet(&mut self, person: &Person, bet_id: &BetId, new_person: bool) {
+        if let Some(mut bet_ids) = self.bets_by_person.get(person) {
+            bet_ids.remove(bet_id);
+            if bet_ids.is_empty() {
+                self.bets_by_person.remove(person);
+            } else {
+                self.bets_by_person.insert(person, &bet_ids);
+            }
+        }
+        if let Some(mut record) = self.persons.get(person) {
+            record.bet_count = record.bet_count.saturating_sub(1);
+            if new_person && record.bet_count == 0 {
+                self.persons.remove(person);
+            } else {
+                self.persons.insert(person, &record);
+            }
+        }
+    }
+
+    pub(crate) fn internal_index_person_bet(&mut self, person: &Person, bet_id: &BetId) {
+        let mut bet_ids = self.bets_by_person.get(person).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::PersonBets { person: person.clone() }.try_to_vec().unwrap())
+        });
+        bet_ids.insert(bet_id);
+        self.bets_by_person.insert(person, &bet_ids);
+    }
+}
+
+fn wrap_person(person: Person, record: PersonRecord) -> WrappedPerson {
+    WrappedPerson {
+        person,
+        alias: record.alias,
+        reference_hash: record.reference_hash,
+        created: record.created,
+        creator: record.creator,
+        status: record.status,
+        bet_count: U64(record.bet_count),
+        account_id: record.account_id,
+        moderated: record.moderated,
+    }
+}
+
+fn assert_valid_person_details(alias: &Option<String>, reference_hash: &Option<Base64VecU8>) {
+    if matches!(alias, Some(alias) if alias.is_empty() || alias.len() > MAX_ALIAS_LEN) {
+        env::panic_str("ERR_INVALID alias");
+    }
+    if matches!(reference_hash, Some(reference_hash) if reference_hash.0.len() != 32) {
+        env::panic_str("ERR_INVALID reference_hash");
+    }
+}
+
+/// Accepts a CIDv0 (base58btc, "Qm" + 44 characters) or a CIDv1 in the base32 multibase IPFS
+/// gateways hand out ("b" + lowercase base32).
+pub fn is_valid_cid(cid: &str) -> bool {
+    let bytes = cid.as_bytes();
+
+    if bytes.len() == 46 && cid.starts_with("Qm") {
+        // NOTE: base58 leaves out 0, O, I and l
+        return bytes.iter().all(|c| matches!(*c,
+            b'1'..=b'9' | b'A'..=b'H' | b'J'..=b'N' | b'P'..=b'Z' | b'a'..=b'k' | b'm'..=b'z'
+        ));
+    }
+
+    bytes.len() >= MIN_CID_LEN
+        && bytes.len() <= MAX_CID_LEN
+        && bytes[0] == b'b'
+        && bytes.iter().all(|c| matches!(*c, b'a'..=b'z' | b'2'..=b'7'))
+}
+
+pub(crate) fn assert_valid_cid(cid: &str) {
+    if !is_valid_cid(cid) {
+        env::panic_str(("ERR_INVALID person: ".to_owned() + cid).as_str());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::*;
+
+use near_sdk::json_types::U128;
+use near_sdk::{EpochHeight, PromiseResult, serde_json};
+
+/// What the contract holds with one staking pool: principal still staked, principal and yield
+/// unstaked and waiting to be withdrawn, and yield already attributed to stakes. `total_balance`
+/// is the pool's own figure from the last `get_account_total_balance` call.
+///
+/// Yield is shared out through `yield_per_share`, which grows by every attributed amount over
+/// the principal earning at the time. A stake's part is its principal times the growth since its
+/// own checkpoint, worked out only when the stake is touched.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakePool {
+    pub staked: Balance,
+    pub unstaked: Balance,
+    pub yield_balance: Balance,
+    pub total_balance: Balance,
+    pub epoch: EpochHeight,
+    /// Principal of the stakes still earning here, settling bets and cancelled remainders left.
+    pub shares: Balance,
+    /// Yield attributed per unit of principal since the pool was first used, scaled by
+    /// `YIELD_PER_SHARE_PRECISION`.
+    pub yield_per_share: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedStakePool {
+    pub pool: AccountId,
+    pub staked: U128,
+    pub unstaked: U128,
+    pub yield_balance: U128,
+    pub total_balance: U128,
+    pub epoch: EpochHeight,
+    pub shares: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn pools(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedStakePool> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.pools
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(pool, stake_pool)| WrappedStakePool {
+                pool,
+                staked: U128(stake_pool.staked),
+                unstaked: U128(stake_pool.unstaked),
+                yield_balance: U128(stake_pool.yield_balance),
+                total_balance: U128(stake_pool.total_balance),
+                epoch: stake_pool.epoch,
+                shares: U128(stake_pool.shares),
+            })
+            .collect()
+    }
+
+    /// Reads the contract's balance from a pool, whatever it grew by since the last read is
+    /// attributed to the stakes held there.
+    pub fn update_pool_yield(&mut self, pool: AccountId) -> Promise {
+        if self.pools.get(&pool).is_none() {
+            env::panic_str(("ERR_DOES_NOT_EXIST pool: ".to_owned() + pool.as_str()).as_str());
+        }
+
+        ext_get_account_total_balance::ext(pool.clone())
+            .get_account_total_balance(env::current_account_id())
+            .then(ext_self::ext(env::current_account_id()).pool_balance_callback(pool))
+    }
+
+    #[private]
+    pub fn pool_balance_callback(&mut self, pool: AccountId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                let total_balance: U128 = serde_json::from_slice(&result).unwrap();
+                let accrued = self.internal_accrue_pool_yield(&pool, total_balance.0);
+
+                format!("{} accrued", accrued)
+            },
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_pool_staked(&mut self, pool: &AccountId, amount: Balance) {
+        let stake_pool = self.pools.get(pool).unwrap_or_default();
+        self.pools.insert(pool, &StakePool {
+            staked: stake_pool.staked + amount,
+            ..stake_pool
+        });
+    }
+
+    pub(crate) fn internal_pool_deposit_reverted(&mut self, pool: &AccountId, amount: Balance) {
+        let stake_pool = self.pools.get(pool).unwrap_or_default();
+        self.pools.insert(pool, &StakePool {
+            staked: stake_pool.staked.saturating_sub(amount),
+            ..stake_pool
+        });
+    }
+
+    pub(crate) fn internal_pool_unstaked(&mut self, pool: &AccountId, amount: Balance, yield_balance: Balance) {
+        let stake_pool = self.pools.get(pool).unwrap_or_default();
+        self.pools.insert(pool, &StakePool {
+            staked: stake_pool.staked.saturating_sub(amount),
+            yield_balance: stake_pool.yield_balance.saturating_sub(yield_balance),
+            unstaked: stake_pool.unstaked + amount + yield_balance,
+            ..stake_pool
+        });
+    }
+
+    pub(crate) fn internal_pool_withdrawn(&mut self, pool: &AccountId, amount: Balance) {
+        let stake_pool = self.pools.get(pool).unwrap_or_default();
+        self.pools.insert(pool, &StakePool {
+            unstaked: stake_pool.unstaked.saturating_sub(amount),
+            ..stake_pool
+        });
+    }
+
+    /// Starts a landed stake earning in its pool. Returns the checkpoint the stake starts from.
+    pub(crate) fn internal_pool_joined(&mut self, pool: &AccountId, amount: Balance) -> u128 {
+        let stake_pool = self.pools.get(pool).unwrap_or_default();
+        self.pools.insert(pool, &StakePool {
+            shares: stake_pool.shares + amount,
+            ..stake_pool
+        });
+
+        stake_pool.yield_per_share
+    }
+
+    /// Yield a stake earned so far, its checkpointed yield plus its part of the pool's growth since.
+    pub(crate) fn internal_stake_yield(&self, stake_id: &StakeId, stake: &Stake) -> Balance {
+        let earning = match self.accounts.get(&stake.staker).and_then(|account| account.earnings.get(stake_id)) {
+            Some(earning) => earning,
+            None => return 0,
+        };
+
+        earning.yield_balance + earning.yield_per_share.map_or(0, |yield_per_share| {
+            pending_yield(stake.amount, self.pools.get(&stake.pool).unwrap_or_default().yield_per_share, yield_per_share)
+        })
+    }
+
+    /// Moves what a stake earned since its last checkpoint into its earning, `leaving` of its
+    /// principal stops earning from here on. A stake leaving whole keeps its yield but earns no more.
+    pub(crate) fn internal_checkpoint_yield(&mut self, stake_id: &StakeId, stake: &Stake, leaving: Balance) {
+        let mut account = match self.accounts.get(&stake.staker) {
+            Some(account) => account,
+            None => return,
+        };
+        let earning = match account.earnings.get(stake_id) {
+            Some(earning) => earning,
+            None => return,
+        };
+        let paid = match earning.yield_per_share {
+            Some(paid) => paid,
+            None => return,
+        };
+        let stake_pool = self.pools.get(&stake.pool).unwrap_or_default();
+
+        account.earnings.insert(stake_id, &StakeEarning {
+            epochs: (earning.epochs.0, env::epoch_height()),
+            yield_balance: earning.yield_balance + pending_yield(stake.amount, stake_pool.yield_per_share, paid),
+            yield_per_share: if leaving >= stake.amount { None } else { Some(stake_pool.yield_per_share) },
+        });
+        self.accounts.insert(&stake.staker, &account);
+        self.pools.insert(&stake.pool, &StakePool {
+            shares: stake_pool.shares.saturating_sub(leaving.min(stake.amount)),
+            ..stake_pool
+        });
+    }
+
+    /// Attributes the growth of a pool's balance to the principal earning there, without touching
+    /// a single stake. Growth while nothing earns stays unattributed until the next read. Returns
+    /// the amount attributed.
+    pub(crate) fn internal_accrue_pool_yield(&mut self, pool: &AccountId, total_balance: Balance) -> Balance {
+        let stake_pool = self.pools.get(pool).unwrap_or_default();
+        let epoch = env::epoch_height();
+        let growth = total_balance.saturating_sub(stake_pool.staked + stake_pool.unstaked + stake_pool.yield_balance);
+
+        let (accrued, yield_per_share) = if growth > 0 && stake_pool.shares > 0 {
+            let increase = U256::from(growth) * U256::from(YIELD_PER_SHARE_PRECISION) / U256::from(stake_pool.shares);
+            // rounding leaves dust in the pool, it is picked up by the next read
+            let accrued = (increase * U256::from(stake_pool.shares) / U256::from(YIELD_PER_SHARE_PRECISION)).as_u128();
+            (accrued, stake_pool.yield_per_share + increase.as_u128())
+        } else {
+            (0, stake_pool.yield_per_share)
+        };
+
+        self.pools.insert(pool, &StakePool {
+            yield_balance: stake_pool.yield_balance + accrued,
+            total_balance,
+            epoch,
+            yield_per_share,
+            ..stake_pool
+        });
+        EventKind::YieldAccrued(vec![YieldAccruedData {
+            pool,
+            amount: U128(accrued),
+            total_balance: U128(total_balance),
+            epoch,
+        }])
+        .emit();
+
+        accrued
+    }
+}
+
+/// What `amount` of principal earned while the pool's yield per share went from `paid` to `current`.
+fn pending_yield(amount: Balance, current: u128, paid: u128) -> Balance {
+    (U256::from(amount) * U256::from(current.saturating_sub(paid)) / U256::from(YIELD_PER_SHARE_PRECISION)).as_u128()
+}
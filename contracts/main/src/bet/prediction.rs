@@ -0,0 +1,65 @@
+use crate::*;
+
+use near_sdk::Timestamp;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Direction {
+    Reduction,
+    Augmentation,
+}
+
+/// What a bet predicts happens to the person by its end. A back stakes on it coming true, a lay
+/// on it not.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Prediction {
+    Reduction,
+    Augmentation,
+    NoChange,
+    /// A change of at least `cup_sizes` in the given direction.
+    Threshold { direction: Direction, cup_sizes: u8 },
+}
+
+impl Prediction {
+    pub(crate) fn assert_valid(&self) {
+        if let Prediction::Threshold { cup_sizes, .. } = self {
+            if *cup_sizes == 0 || *cup_sizes > MAX_CUP_SIZES {
+                env::panic_str(("ERR_INVALID cup_sizes: ".to_owned() + &cup_sizes.to_string()).as_str());
+            }
+        }
+    }
+
+    /// Shortest time a bet on it can run. Staying the same is only worth betting on over a longer
+    /// stretch and every cup size of a threshold takes time of its own.
+    pub(crate) fn min_duration(&self, min_duration: u64) -> u64 {
+        match self {
+            Prediction::Reduction | Prediction::Augmentation => min_duration,
+            Prediction::NoChange => 3 * min_duration,
+            Prediction::Threshold { cup_sizes, .. } => min_duration * u64::from(*cup_sizes),
+        }
+    }
+
+    /// The proposition put to the voting pool, a yes vote means the prediction came true.
+    pub(crate) fn motion(&self, person: &str, end: Timestamp) -> String {
+        let proposition = match self {
+            Prediction::Reduction => "has had a reduction".to_string(),
+            Prediction::Augmentation => "has had an augmentation".to_string(),
+            Prediction::NoChange => "has had neither a reduction nor an augmentation".to_string(),
+            Prediction::Threshold { direction, cup_sizes } => format!(
+                "is at least {} cup size(s) {} than at the time of the bet",
+                cup_sizes,
+                match direction {
+                    Direction::Reduction => "smaller",
+                    Direction::Augmentation => "larger",
+                },
+            ),
+        };
+
+        format!("{} {} by {}", person, proposition, end)
+    }
+}
@@ -0,0 +1,631 @@
+use crate::*;
+
+use near_sdk::collections::Vector;
+use near_sdk::json_types::{U64, U128};
+use near_sdk::{EpochHeight, PromiseResult, serde_json};
+
+/// Where a bet's settlement has got to. Every step that walks the bet's stakes does so in batches
+/// of at most `Config.settlement_batch_size`, the cursor keeps the place between calls.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SettlementStep {
+    /// Collecting the stake ids and the voting pool.
+    Pending,
+    /// Waiting on the motion, or on its result.
+    MotionRequested,
+    /// Winner known, tallying what each pool holds and then unstaking and withdrawing it.
+    Resolved,
+    PayingOut,
+    Done,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SettlementCursor {
+    pub step: SettlementStep,
+    /// Index set being collected while Pending, see `BET_INDEX_SETS`.
+    pub set: u8,
+    /// Offset into that set while Pending, into `stake_ids` after.
+    pub index: u64,
+    /// The bet's stakes as they stood when it closed, later steps walk them in this order.
+    pub stake_ids: Vector<StakeId>,
+    pub voting_pool: Vec<AccountId>,
+    /// Block the motion was last asked for at, no other request goes out before
+    /// `MOTION_REQUEST_TIMEOUT` has passed or the request came back failed.
+    pub motion_requested_at: Option<BlockHeight>,
+    /// A voided bet skips the motion and refunds every stake whole.
+    pub void: bool,
+    pub winner: Option<Position>,
+    /// Per pool tally, written to the `Settlement` once complete.
+    pub pools: Vec<PoolSettlement>,
+}
+
+impl SettlementCursor {
+    /// Whether a motion request went out and has neither come back failed nor timed out.
+    pub fn motion_in_flight(&self) -> bool {
+        self.motion_requested_at
+            .is_some_and(|requested_at| env::block_height() < requested_at + MOTION_REQUEST_TIMEOUT)
+    }
+}
+
+/// Order the bet index sets are collected in.
+const BET_INDEX_SETS: [(Position, bool); 4] = [
+    (Position::Back, false),
+    (Position::Back, true),
+    (Position::Lay, false),
+    (Position::Lay, true),
+];
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SettlementStatus {
+    Resolved,
+    Unstaking,
+    Unstaked,
+    Withdrawing,
+    Withdrawn,
+    PaidOut,
+}
+
+/// The part of a bet's principal held by one staking pool, each pool is unstaked and withdrawn
+/// on its own.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolSettlement {
+    pub pool: AccountId,
+    pub amount: Balance,
+    pub yield_balance: Balance,
+    pub epoch: EpochHeight,
+    pub status: SettlementStatus,
+}
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Settlement {
+    /// None when the bet was voided and every stake gets its whole amount back.
+    pub winner: Option<Position>,
+    pub pools: Vec<PoolSettlement>,
+    pub status: SettlementStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedPoolSettlement {
+    pub pool: AccountId,
+    pub amount: U128,
+    pub yield_balance: U128,
+    pub epoch: EpochHeight,
+    pub status: SettlementStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedSettlement {
+    pub bet_id: BetId,
+    pub step: SettlementStep,
+    /// Stakes the current step has worked through.
+    pub progress: U64,
+    pub stakes: U64,
+    pub winner: Option<Position>,
+    pub pools: Vec<WrappedPoolSettlement>,
+    /// None until the pools are tallied.
+    pub status: Option<SettlementStatus>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Drives a bet from its end to paid out stakes, one batch per call. An ended bet first has its
+    /// stakes collected and is put to the voting pool, a voided one skips the vote. Once the motion
+    /// is resolved the bet's principal is tallied per pool, unstaked and (once the pools release
+    /// it) withdrawn, and then every stake is paid.
+    pub fn settle_bet(&mut self, bet_id: BetId) -> String {
+        self.assert_not_paused(Pausable::Settlement);
+        let bet = self.bets.get(&bet_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST bet_id: ".to_owned() + &bet_id.to_string()).as_str())
+        });
+        if self.settlement_cursors.get(&bet_id).is_none() {
+            if self.internal_person_withdrawn(&bet.person) {
+                self.internal_void_bet(&bet_id);
+            } else if bet.motion_id.is_none() && bet.end > env::block_timestamp() {
+                env::panic_str(("ERR_NO_MOTION bet_id: ".to_owned() + &bet_id.to_string()).as_str());
+            } else {
+                self.internal_open_settlement(&bet_id, false);
+            }
+        }
+
+        let mut budget = u64::from(self.config.settlement_batch_size);
+        self.internal_settle_batch(&bet_id, &mut budget, None)
+    }
+
+    pub fn settlement(&self, bet_id: BetId) -> Option<WrappedSettlement> {
+        let cursor = self.settlement_cursors.get(&bet_id)?;
+        let settlement = self.settlements.get(&bet_id);
+
+        Some(WrappedSettlement {
+            bet_id,
+            step: cursor.step,
+            progress: U64(cursor.index),
+            stakes: U64(cursor.stake_ids.len()),
+            winner: settlement.as_ref().map_or(cursor.winner, |settlement| settlement.winner.clone()),
+            pools: settlement
+                .as_ref()
+                .map_or(&cursor.pools, |settlement| &settlement.pools)
+                .iter()
+                .map(|pool_settlement| WrappedPoolSettlement {
+                    pool: pool_settlement.pool.clone(),
+                    amount: U128(pool_settlement.amount),
+                    yield_balance: U128(pool_settlement.yield_balance),
+                    epoch: pool_settlement.epoch,
+                    status: pool_settlement.status.clone(),
+                })
+                .collect(),
+            status: settlement.map(|settlement| settlement.status),
+        })
+    }
+
+    #[private]
+    pub fn settle_motion_callback(&mut self, bet_id: BetId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                let mut cursor = self.settlement_cursors.get(&bet_id).unwrap();
+                if cursor.step != SettlementStep::MotionRequested {
+                    return "already settling".to_string();
+                }
+                let motions: Option<Vec<JsonMotion>> = serde_json::from_slice(&result).unwrap();
+                let motion = motions.unwrap_or_default().into_iter().next().map(|json_motion| json_motion.motion);
+                // a yes vote means the prediction came true, which is what the backers staked on
+                let winner = match motion.map(|motion| motion.status) {
+                    Some(MotionStatus::Adopted) => Position::Back,
+                    Some(MotionStatus::Rejected) => Position::Lay,
+                    _ => return "not resolved".to_string(),
+                };
+                cursor.step = SettlementStep::Resolved;
+                cursor.winner = Some(winner);
+                cursor.voting_pool.clear();
+                cursor.index = 0;
+                self.settlement_cursors.insert(&bet_id, &cursor);
+                "ok".to_string()
+            },
+        }
+    }
+
+    #[private]
+    pub fn settle_unstake_callback(&mut self, bet_id: BetId, pool: AccountId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        let (status, result) = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            // nothing left the pool, the next settle_bet call tries again
+            PromiseResult::Failed => (SettlementStatus::Resolved, "oops!"),
+            PromiseResult::Successful(_result) => (SettlementStatus::Unstaked, "ok"),
+        };
+        let pool_settlement = self.internal_update_pool_settlement(&bet_id, &pool, status.clone());
+        if status == SettlementStatus::Unstaked {
+            self.internal_pool_unstaked(&pool, pool_settlement.amount, pool_settlement.yield_balance);
+        }
+
+        result.to_string()
+    }
+
+    #[private]
+    pub fn settle_withdraw_callback(&mut self, bet_id: BetId, pool: AccountId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        let (status, result) = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => (SettlementStatus::Unstaked, "oops!"),
+            PromiseResult::Successful(_result) => (SettlementStatus::Withdrawn, "ok"),
+        };
+        let pool_settlement = self.internal_update_pool_settlement(&bet_id, &pool, status.clone());
+        if status == SettlementStatus::Withdrawn {
+            self.internal_pool_withdrawn(&pool, pool_settlement.amount + pool_settlement.yield_balance);
+
+            let settlement = self.settlements.get(&bet_id).unwrap();
+            if settlement.pools.iter().all(|pool_settlement| pool_settlement.status == SettlementStatus::Withdrawn) {
+                let mut cursor = self.settlement_cursors.get(&bet_id).unwrap();
+                cursor.step = SettlementStep::PayingOut;
+                cursor.index = 0;
+                self.settlement_cursors.insert(&bet_id, &cursor);
+                // the first batch goes out right away, settle_bet pays the rest. A paused
+                // settlement leaves the cursor parked until settle_bet picks it up again
+                if !self.internal_paused(Pausable::Settlement) {
+                    let mut budget = u64::from(self.config.settlement_batch_size);
+                    self.internal_settle_batch(&bet_id, &mut budget, None);
+                }
+            }
+        }
+
+        result.to_string()
+    }
+}
+
+impl Contract {
+    /// Settles a bet without a winner, every stake is refunded in full with its yield. Bets whose
+    /// motion is already resolved are left alone.
+    pub(crate) fn internal_void_bet(&mut self, bet_id: &BetId) -> bool {
+        match self.settlement_cursors.get(bet_id) {
+            None => {
+                self.internal_open_settlement(bet_id, true);
+                true
+            },
+            Some(mut cursor) if cursor.step <= SettlementStep::MotionRequested => {
+                cursor.void = true;
+                // the stakes are all collected, only the motion is skipped
+                if cursor.step == SettlementStep::MotionRequested {
+                    cursor.step = SettlementStep::Resolved;
+                    cursor.voting_pool.clear();
+                    cursor.index = 0;
+                }
+                self.settlement_cursors.insert(bet_id, &cursor);
+                true
+            },
+            Some(_) => false,
+        }
+    }
+
+    /// Closes a bet to new stakes and cancellations and starts collecting its stakes.
+    pub(crate) fn internal_open_settlement(&mut self, bet_id: &BetId, void: bool) {
+        self.settlement_cursors.insert(bet_id, &SettlementCursor {
+            step: SettlementStep::Pending,
+            set: 0,
+            index: 0,
+            stake_ids: Vector::new(StorageKey::SettlementStakes { bet_id: bet_id.clone() }.try_to_vec().unwrap()),
+            voting_pool: vec![],
+            motion_requested_at: None,
+            void,
+            winner: None,
+            pools: vec![],
+        });
+    }
+
+    /// Whether a bet takes no more stakes or cancellations, it closes once its settlement opens.
+    pub(crate) fn internal_bet_settling(&self, bet_id: &BetId) -> bool {
+        self.settlement_cursors.get(bet_id).is_some()
+    }
+
+    /// Opens the settlement of each bet that has none yet and works the bets on in turn until the
+    /// batch runs out. Returns how many bets it got to.
+    pub(crate) fn internal_settle_bets(&mut self, bet_ids: Vec<BetId>, keeper: Option<AccountId>) -> u64 {
+        let mut budget = u64::from(self.config.settlement_batch_size);
+        let mut count = 0;
+        for bet_id in bet_ids {
+            if budget == 0 {
+                break;
+            }
+            if !self.internal_bet_settling(&bet_id) {
+                self.internal_open_settlement(&bet_id, false);
+            }
+            self.internal_settle_batch(&bet_id, &mut budget, keeper.clone());
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Adds a stake whose deposit landed after its bet closed to the bet's settlement, unless the
+    /// collection is still going to reach it. Once the pools are being tallied it is too late, the
+    /// stake is voided instead and refunded whole through `refund_cancelled_stake`.
+    pub(crate) fn internal_settlement_stake_landed(&mut self, stake_id: &StakeId, stake: &Stake) {
+        let mut cursor = self.settlement_cursors.get(&stake.bet_id).unwrap();
+        let set = BET_INDEX_SETS
+            .iter()
+            .position(|(position, matched)| position == &stake.position && !matched)
+            .unwrap() as u8;
+        let collected = match cursor.step {
+            SettlementStep::Pending => set < cursor.set,
+            SettlementStep::MotionRequested => true,
+            _ => false,
+        };
+        if collected {
+            cursor.stake_ids.push(stake_id);
+            self.settlement_cursors.insert(&stake.bet_id, &cursor);
+        } else if cursor.step >= SettlementStep::Resolved {
+            self.internal_cancel_stake(stake_id, stake.clone());
+        }
+    }
+
+    /// Works a bet's settlement on for as long as the batch lasts, each stake collected, tallied
+    /// or paid takes one from `budget`. Returns where the bet got to.
+    pub(crate) fn internal_settle_batch(
+        &mut self,
+        bet_id: &BetId,
+        budget: &mut u64,
+        keeper: Option<AccountId>,
+    ) -> String {
+        let mut cursor = self.settlement_cursors.get(bet_id).unwrap();
+        loop {
+            match cursor.step {
+                SettlementStep::Pending => {
+                    if !self.internal_collect_stakes(bet_id, &mut cursor, budget) {
+                        self.settlement_cursors.insert(bet_id, &cursor);
+                        return format!("{} stake(s) collected...", cursor.stake_ids.len());
+                    }
+                    // nobody to put the motion to, what is there goes back whole
+                    if cursor.void || cursor.voting_pool.is_empty() {
+                        cursor.void = true;
+                        cursor.voting_pool.clear();
+                        cursor.step = SettlementStep::Resolved;
+                        cursor.index = 0;
+                        continue;
+                    }
+                    cursor.step = SettlementStep::MotionRequested;
+                },
+                SettlementStep::MotionRequested => {
+                    match self.bets.get(bet_id).unwrap().motion_id {
+                        // the request is still on its way, asking again would open a second motion
+                        None if cursor.motion_in_flight() => {
+                            self.settlement_cursors.insert(bet_id, &cursor);
+                            return "motion requested...".to_string();
+                        },
+                        // never put to the vote, or the motion got lost on the way
+                        None => {
+                            cursor.motion_requested_at = Some(env::block_height());
+                            self.settlement_cursors.insert(bet_id, &cursor);
+                            self.internal_request_bet_motion(bet_id, cursor.voting_pool.clone(), keeper);
+                            return "motion requested...".to_string();
+                        },
+                        Some(motion_id) => {
+                            self.settlement_cursors.insert(bet_id, &cursor);
+                            ext_voting::ext(self.config.voting_contract.clone())
+                                .view_motions(None, None, Some(vec![motion_id]), None)
+                                .then(ext_self::ext(env::current_account_id()).settle_motion_callback(bet_id.clone()));
+                            return "resolving...".to_string();
+                        },
+                    }
+                },
+                SettlementStep::Resolved => {
+                    if let Some(settlement) = self.settlements.get(bet_id) {
+                        self.settlement_cursors.insert(bet_id, &cursor);
+                        let started = self.internal_advance_settlement(bet_id, settlement);
+                        if started == 0 {
+                            env::panic_str(("ERR_PENDING bet_id: ".to_owned() + bet_id).as_str());
+                        }
+                        return format!("{} pool call(s) pending...", started);
+                    }
+                    if !self.internal_tally_pools(&mut cursor, budget) {
+                        self.settlement_cursors.insert(bet_id, &cursor);
+                        return format!("{} of {} stake(s) tallied...", cursor.index, cursor.stake_ids.len());
+                    }
+                    let pools = std::mem::take(&mut cursor.pools);
+                    EventKind::SettlementStarted(vec![SettlementStartedData {
+                        bet_id,
+                        winner: &cursor.winner,
+                        pools: pools
+                            .iter()
+                            .map(|pool_settlement| (&pool_settlement.pool, U128(pool_settlement.amount + pool_settlement.yield_balance)))
+                            .collect(),
+                    }])
+                    .emit();
+                    if pools.is_empty() {
+                        // every stake was cancelled before the bet ended, there is nothing left to unstake
+                        cursor.step = SettlementStep::PayingOut;
+                        cursor.index = 0;
+                        continue;
+                    }
+                    self.settlement_cursors.insert(bet_id, &cursor);
+                    let started = self.internal_advance_settlement(bet_id, Settlement {
+                        winner: cursor.winner.clone(),
+                        pools,
+                        status: SettlementStatus::Resolved,
+                    });
+                    return format!("{} pool call(s) pending...", started);
+                },
+                SettlementStep::PayingOut => {
+                    if !self.internal_pay_out(bet_id, &mut cursor, budget) {
+                        self.settlement_cursors.insert(bet_id, &cursor);
+                        return format!("{} of {} stake(s) paid...", cursor.index, cursor.stake_ids.len());
+                    }
+                    let settlement = self.settlements.get(bet_id).unwrap_or(Settlement {
+                        winner: cursor.winner.clone(),
+                        pools: vec![],
+                        status: SettlementStatus::PaidOut,
+                    });
+                    self.settlements.insert(bet_id, &Settlement {
+                        status: SettlementStatus::PaidOut,
+                        ..settlement
+                    });
+                    // the collected ids did their job, dropping them frees their storage
+                    cursor.stake_ids.clear();
+                    cursor.step = SettlementStep::Done;
+                    self.settlement_cursors.insert(bet_id, &cursor);
+                    return "paid out".to_string();
+                },
+                SettlementStep::Done => {
+                    env::panic_str(("ERR_SETTLED bet_id: ".to_owned() + bet_id).as_str());
+                },
+            }
+        }
+    }
+
+    /// Copies the bet's stake ids from its index into the cursor, and their stakers into the
+    /// voting pool. Nothing reshuffles the index of a closed bet, new stakes only ever get
+    /// appended, so the place stays valid between calls. Returns whether every set is collected.
+    fn internal_collect_stakes(&self, bet_id: &BetId, cursor: &mut SettlementCursor, budget: &mut u64) -> bool {
+        while (cursor.set as usize) < BET_INDEX_SETS.len() {
+            let (position, matched) = &BET_INDEX_SETS[cursor.set as usize];
+            let stake_ids = self.internal_bet_index(position, *matched).get(bet_id);
+            let len = stake_ids.as_ref().map_or(0, |stake_ids| stake_ids.len());
+            while cursor.index < len {
+                if *budget == 0 {
+                    return false;
+                }
+                let stake_id = stake_ids.as_ref().unwrap().as_vector().get(cursor.index).unwrap();
+                let staker = self.stakes.get(&stake_id).unwrap().staker;
+                if !cursor.voting_pool.contains(&staker) {
+                    cursor.voting_pool.push(staker);
+                }
+                cursor.stake_ids.push(&stake_id);
+                cursor.index += 1;
+                *budget -= 1;
+            }
+            cursor.set += 1;
+            cursor.index = 0;
+        }
+
+        true
+    }
+
+    /// Adds up what the collected stakes hold per pool, each stake stops earning once tallied so
+    /// what gets unstaked matches what gets paid. Returns whether every stake is tallied.
+    fn internal_tally_pools(&mut self, cursor: &mut SettlementCursor, budget: &mut u64) -> bool {
+        while cursor.index < cursor.stake_ids.len() {
+            if *budget == 0 {
+                return false;
+            }
+            let stake_id = cursor.stake_ids.get(cursor.index).unwrap();
+            let stake = self.stakes.get(&stake_id).unwrap();
+            cursor.index += 1;
+            *budget -= 1;
+
+            if stake.settled {
+                continue;
+            }
+            self.internal_checkpoint_yield(&stake_id, &stake, stake.amount);
+            let yield_balance = self.internal_stake_yield(&stake_id, &stake);
+            if stake.amount + yield_balance == 0 {
+                continue;
+            }
+            if let Some(pool_settlement) = cursor.pools.iter_mut().find(|pool_settlement| pool_settlement.pool == stake.pool) {
+                pool_settlement.amount += stake.amount;
+                pool_settlement.yield_balance += yield_balance;
+            } else {
+                cursor.pools.push(PoolSettlement {
+                    pool: stake.pool,
+                    amount: stake.amount,
+                    yield_balance,
+                    epoch: env::epoch_height(),
+                    status: SettlementStatus::Resolved,
+                });
+            }
+        }
+
+        true
+    }
+
+    /// Sends the next pool call for every pool of a settlement that is ready for one: an unstake
+    /// once resolved, a withdraw once the unstaked amount is released. Returns how many were sent.
+    fn internal_advance_settlement(&mut self, bet_id: &BetId, mut settlement: Settlement) -> usize {
+        let mut started = 0;
+        for pool_settlement in settlement.pools.iter_mut() {
+            match pool_settlement.status {
+                SettlementStatus::Resolved => {
+                    pool_settlement.status = SettlementStatus::Unstaking;
+                    ext_unstake::ext(pool_settlement.pool.clone()).unstake((pool_settlement.amount + pool_settlement.yield_balance).to_string())
+                        .then(ext_self::ext(env::current_account_id())
+                        .settle_unstake_callback(bet_id.clone(), pool_settlement.pool.clone()));
+                    started += 1;
+                },
+                SettlementStatus::Unstaked if env::epoch_height() - pool_settlement.epoch >= self.config.refund_delay_epochs.into() => {
+                    pool_settlement.status = SettlementStatus::Withdrawing;
+                    ext_withdraw::ext(pool_settlement.pool.clone()).withdraw((pool_settlement.amount + pool_settlement.yield_balance).to_string())
+                        .then(ext_self::ext(env::current_account_id())
+                        .settle_withdraw_callback(bet_id.clone(), pool_settlement.pool.clone()));
+                    started += 1;
+                },
+                _ => (),
+            }
+        }
+        self.settlements.insert(bet_id, &settlement);
+
+        started
+    }
+
+    /// Writes the new status of one pool of a settlement and returns that pool's part.
+    fn internal_update_pool_settlement(
+        &mut self,
+        bet_id: &BetId,
+        pool: &AccountId,
+        status: SettlementStatus,
+    ) -> PoolSettlement {
+        let mut settlement = self.settlements.get(bet_id).unwrap();
+        let pool_settlement = settlement.pools
+            .iter_mut()
+            .find(|pool_settlement| &pool_settlement.pool == pool)
+            .unwrap();
+        pool_settlement.epoch = env::epoch_height();
+        pool_settlement.status = status.clone();
+        let updated = pool_settlement.clone();
+
+        if settlement.pools.iter().all(|pool_settlement| pool_settlement.status == status) {
+            settlement.status = status;
+        }
+        self.settlements.insert(bet_id, &settlement);
+
+        updated
+    }
+
+    /// Pays the next batch of the collected stakes and marks them settled. A winner takes its own
+    /// stake plus everything its matches put against it, a loser only gets its unmatched remainder
+    /// back and a voided bet returns every stake whole. Every stake keeps the yield it earned
+    /// either way, less the protocol fee. Returns whether every stake is paid.
+    fn internal_pay_out(&mut self, bet_id: &BetId, cursor: &mut SettlementCursor, budget: &mut u64) -> bool {
+        let winner = &cursor.winner;
+        let mut payouts: Vec<PayoutData> = vec![];
+
+        while cursor.index < cursor.stake_ids.len() && *budget > 0 {
+            let stake_id = cursor.stake_ids.get(cursor.index).unwrap();
+            let stake = self.stakes.get(&stake_id).unwrap();
+            cursor.index += 1;
+            *budget -= 1;
+            if stake.settled {
+                continue;
+            }
+
+            let yield_balance = self.internal_stake_yield(&stake_id, &stake);
+            let protocol_fee = yield_balance * u128::from(self.config.protocol_fee_bps) / 10_000;
+            self.protocol_fees += protocol_fee;
+            let payout = yield_balance - protocol_fee + match winner {
+                None => stake.amount,
+                Some(winner) if &stake.position == winner => stake.amount + self.internal_counter(&stake_id),
+                Some(_) => stake.unmatched,
+            };
+
+            // the earning is paid out with the stake, dropping it gives the staker its storage back
+            if let Some(mut account) = self.accounts.get(&stake.staker) {
+                account.earnings.remove(&stake_id);
+                self.accounts.insert(&stake.staker, &account);
+            }
+            self.internal_settle_storage(&stake.staker, stake.storage_used, 0);
+            let stake = Stake {
+                settled: true,
+                storage_used: 0,
+                ..stake
+            };
+            self.stakes.insert(&stake_id, &stake);
+            self.internal_index_stake(&stake_id, &stake);
+            self.internal_remove_principal(&stake.staker, stake.amount);
+            if payout > 0 {
+                Promise::new(stake.staker.clone()).transfer(payout);
+            }
+            payouts.push(PayoutData {
+                stake_id,
+                staker: stake.staker,
+                amount: U128(payout),
+            });
+        }
+        if !payouts.is_empty() {
+            EventKind::BetSettled(vec![BetSettledData {
+                bet_id,
+                winner,
+                payouts,
+            }])
+            .emit();
+        }
+
+        cursor.index == cursor.stake_ids.len()
+    }
+}
@@ -0,0 +1,237 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+use near_sdk::{PromiseResult, Timestamp, serde_json};
+
+pub type TakedownId = u64;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TakedownTarget {
+    Person(Person),
+    Bet(BetId),
+}
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TakedownStatus {
+    /// Waiting on the voting pool.
+    Proposed,
+    Enacted,
+    Rejected,
+}
+
+/// Record of a takedown, kept after it is enacted so the reason stays on file.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Takedown {
+    pub target: TakedownTarget,
+    pub reason: String,
+    pub requester: AccountId,
+    pub motion_id: Option<MotionId>,
+    pub created: Timestamp,
+    pub status: TakedownStatus,
+    /// Bets voided when it was enacted, a person's further bets go through `void_person_bets`.
+    pub voided: Vec<BetId>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedTakedown {
+    pub takedown_id: U64,
+    pub target: TakedownTarget,
+    pub reason: String,
+    pub requester: AccountId,
+    pub motion_id: Option<MotionId>,
+    pub created: Timestamp,
+    pub status: TakedownStatus,
+    pub voided: Vec<BetId>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Takes a person or bet down right away. Every open bet it covers is voided, which refunds
+    /// each stake through the usual unstake and withdraw calls of `settle_bet`.
+    #[payable]
+    pub fn take_down(&mut self, target: TakedownTarget, reason: String) -> U64 {
+        self.assert_role(Role::Moderator);
+        assert_one_yocto();
+
+        let takedown_id = self.internal_record_takedown(target, reason, TakedownStatus::Enacted);
+        self.internal_enact_takedown(takedown_id);
+
+        U64(takedown_id)
+    }
+
+    /// Puts a takedown to the voting pool, `enact_takedown` carries it out once the motion is
+    /// adopted. The attached deposit pays for the record.
+    #[payable]
+    pub fn propose_takedown(&mut self, target: TakedownTarget, reason: String) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        let takedown_id = self.internal_record_takedown(target, reason.clone(), TakedownStatus::Proposed);
+        internal::refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        ext_voting::ext(self.config.voting_contract.clone())
+            .with_attached_deposit(1)
+            .motions(vec![(
+                Some("takedown".to_string()),
+                format!("takedown {}: {}", takedown_id, reason),
+                None,
+                None,
+                None,
+            )])
+            .then(ext_self::ext(env::current_account_id()).takedown_motion_callback(takedown_id))
+    }
+
+    /// Reads the motion of a proposed takedown and enacts or rejects it once the vote is in.
+    pub fn enact_takedown(&mut self, takedown_id: U64) -> Promise {
+        let takedown = self.takedowns.get(takedown_id.0).unwrap_or_else(|| {
+            env::panic_str(("ERR_DOES_NOT_EXIST takedown_id: ".to_owned() + &takedown_id.0.to_string()).as_str())
+        });
+        if takedown.status != TakedownStatus::Proposed {
+            env::panic_str(("ERR_RESOLVED takedown_id: ".to_owned() + &takedown_id.0.to_string()).as_str());
+        }
+        let motion_id = takedown.motion_id.unwrap_or_else(|| {
+            env::panic_str(("ERR_NO_MOTION takedown_id: ".to_owned() + &takedown_id.0.to_string()).as_str())
+        });
+
+        ext_voting::ext(self.config.voting_contract.clone())
+            .view_motions(None, None, Some(vec![motion_id]), None)
+            .then(ext_self::ext(env::current_account_id()).takedown_resolve_callback(takedown_id.0))
+    }
+
+    pub fn takedowns(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<WrappedTakedown> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        (start as u64..self.takedowns.len())
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .map(|takedown_id| wrap_takedown(takedown_id, self.takedowns.get(takedown_id).unwrap()))
+            .collect()
+    }
+
+    #[private]
+    pub fn takedown_motion_callback(&mut self, takedown_id: TakedownId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                let motions: Vec<Motion> = serde_json::from_slice(&result).unwrap();
+                let mut takedown = self.takedowns.get(takedown_id).unwrap();
+                takedown.motion_id = motions.into_iter().next().map(|motion| motion.motion_id);
+                self.takedowns.replace(takedown_id, &takedown);
+                "ok".to_string()
+            },
+        }
+    }
+
+    #[private]
+    pub fn takedown_resolve_callback(&mut self, takedown_id: TakedownId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                let mut takedown = self.takedowns.get(takedown_id).unwrap();
+                if takedown.status != TakedownStatus::Proposed {
+                    return "already resolved".to_string();
+                }
+                let motions: Option<Vec<JsonMotion>> = serde_json::from_slice(&result).unwrap();
+                let motion = motions.unwrap_or_default().into_iter().next().map(|json_motion| json_motion.motion);
+                match motion.map(|motion| motion.status) {
+                    Some(MotionStatus::Adopted) => {
+                        takedown.status = TakedownStatus::Enacted;
+                        self.takedowns.replace(takedown_id, &takedown);
+                        self.internal_enact_takedown(takedown_id);
+                    },
+                    Some(MotionStatus::Rejected) => {
+                        takedown.status = TakedownStatus::Rejected;
+                        self.takedowns.replace(takedown_id, &takedown);
+                    },
+                    _ => return "not resolved".to_string(),
+                }
+                "ok".to_string()
+            },
+        }
+    }
+}
+
+impl Contract {
+    fn internal_record_takedown(
+        &mut self,
+        target: TakedownTarget,
+        reason: String,
+        status: TakedownStatus,
+    ) -> TakedownId {
+        if reason.is_empty() || reason.len() > MAX_REASON_LEN {
+            env::panic_str("ERR_INVALID reason");
+        }
+        match &target {
+            TakedownTarget::Person(person) => if self.persons.get(person).is_none() {
+                env::panic_str(("ERR_DOES_NOT_EXIST person: ".to_owned() + person).as_str());
+            },
+            TakedownTarget::Bet(bet_id) => if self.bets.get(bet_id).is_none() {
+                env::panic_str(("ERR_DOES_NOT_EXIST bet_id: ".to_owned() + bet_id).as_str());
+            },
+        }
+
+        self.takedowns.push(&Takedown {
+            target,
+            reason,
+            requester: env::predecessor_account_id(),
+            motion_id: None,
+            created: env::block_timestamp(),
+            status,
+            voided: vec![],
+        });
+
+        self.takedowns.len() - 1
+    }
+
+    /// Freezes the target and voids the bets it covers that are not settling yet, a person's
+    /// first `MAX_VOIDED_BETS_PER_CALL` of them. A taken down person takes no stake any more, bets
+    /// left over are voided by `void_person_bets` or `settle_bet`.
+    fn internal_enact_takedown(&mut self, takedown_id: TakedownId) {
+        let mut takedown = self.takedowns.get(takedown_id).unwrap();
+        takedown.voided = match &takedown.target {
+            TakedownTarget::Person(person) => {
+                let mut record = self.persons.get(person).unwrap();
+                record.status = PersonStatus::TakenDown;
+                self.persons.insert(person, &record);
+
+                self.internal_void_person_bets(person, 0, MAX_VOIDED_BETS_PER_CALL)
+            },
+            TakedownTarget::Bet(bet_id) => vec![bet_id.clone()]
+                .into_iter()
+                .filter(|bet_id| self.internal_void_bet(bet_id))
+                .collect(),
+        };
+        self.takedowns.replace(takedown_id, &takedown);
+    }
+}
+
+fn wrap_takedown(takedown_id: TakedownId, takedown: Takedown) -> WrappedTakedown {
+    WrappedTakedown {
+        takedown_id: U64(takedown_id),
+        target: takedown.target,
+        reason: takedown.reason,
+        requester: takedown.requester,
+        motion_id: takedown.motion_id,
+        created: takedown.created,
+        status: takedown.status,
+        voided: takedown.voided,
+    }
+}
@@ -0,0 +1,142 @@
+//! Tunables the admins can change without a redeploy. The compile-time constants in `lib.rs` are
+//! only the values `new` starts from.
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct Config {
+    pub min_stake: Balance,
+    /// Shortest bet in nanoseconds, predictions scale it up, see `Prediction::min_duration`.
+    pub min_duration: u64,
+    /// Epochs between unstaking and withdrawing from a pool.
+    pub refund_delay_epochs: u8,
+    /// Puts each ended bet to its stakers, it has to list this contract among its motion callers.
+    pub voting_contract: AccountId,
+    /// Pool stakes go to while the metadata names none.
+    pub default_stake_pool: AccountId,
+    /// Share of each stake's yield kept at payout, in basis points.
+    pub protocol_fee_bps: u16,
+    /// Paid from the protocol fees for every bet a keeper puts to the vote.
+    pub keeper_fee: Balance,
+    /// Most stakes one call collects, tallies or pays while settling a bet.
+    pub settlement_batch_size: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedConfig {
+    pub min_stake: U128,
+    pub min_duration: U64,
+    pub refund_delay_epochs: u8,
+    pub voting_contract: AccountId,
+    pub default_stake_pool: AccountId,
+    pub protocol_fee_bps: u16,
+    pub keeper_fee: U128,
+    pub settlement_batch_size: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_stake: MIN_STAKE,
+            min_duration: MIN_DURATION,
+            refund_delay_epochs: REFUND_DELAY_EPOCHS,
+            voting_contract: VOTING_CONTRACT.parse().unwrap(),
+            default_stake_pool: DEFAULT_STAKE_POOL.parse().unwrap(),
+            protocol_fee_bps: PROTOCOL_FEE_BPS,
+            keeper_fee: KEEPER_FEE,
+            settlement_batch_size: SETTLEMENT_BATCH_SIZE,
+        }
+    }
+}
+
+impl From<&Config> for WrappedConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            min_stake: U128(config.min_stake),
+            min_duration: U64(config.min_duration),
+            refund_delay_epochs: config.refund_delay_epochs,
+            voting_contract: config.voting_contract.clone(),
+            default_stake_pool: config.default_stake_pool.clone(),
+            protocol_fee_bps: config.protocol_fee_bps,
+            keeper_fee: U128(config.keeper_fee),
+            settlement_batch_size: config.settlement_batch_size,
+        }
+    }
+}
+
+impl From<WrappedConfig> for Config {
+    fn from(config: WrappedConfig) -> Self {
+        Self {
+            min_stake: config.min_stake.0,
+            min_duration: config.min_duration.0,
+            refund_delay_epochs: config.refund_delay_epochs,
+            voting_contract: config.voting_contract,
+            default_stake_pool: config.default_stake_pool,
+            protocol_fee_bps: config.protocol_fee_bps,
+            keeper_fee: config.keeper_fee.0,
+            settlement_batch_size: config.settlement_batch_size,
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn assert_valid(&self) {
+        if self.min_stake == 0 {
+            env::panic_str("ERR_INVALID min_stake: 0");
+        }
+        if self.min_duration > MAX_MIN_DURATION {
+            env::panic_str(("ERR_INVALID min_duration: ".to_owned() + &self.min_duration.to_string()).as_str());
+        }
+        if self.refund_delay_epochs > MAX_REFUND_DELAY_EPOCHS {
+            env::panic_str(("ERR_INVALID refund_delay_epochs: ".to_owned() + &self.refund_delay_epochs.to_string()).as_str());
+        }
+        if self.protocol_fee_bps > MAX_PROTOCOL_FEE_BPS {
+            env::panic_str(("ERR_INVALID protocol_fee_bps: ".to_owned() + &self.protocol_fee_bps.to_string()).as_str());
+        }
+        if !(1..=MAX_SETTLEMENT_BATCH_SIZE).contains(&self.settlement_batch_size) {
+            env::panic_str(("ERR_INVALID settlement_batch_size: ".to_owned() + &self.settlement_batch_size.to_string()).as_str());
+        }
+        // the contract cannot hold its own motions or stake with itself
+        if self.voting_contract == env::current_account_id() {
+            env::panic_str(("ERR_INVALID voting_contract: ".to_owned() + self.voting_contract.as_str()).as_str());
+        }
+        if self.default_stake_pool == env::current_account_id() {
+            env::panic_str(("ERR_INVALID default_stake_pool: ".to_owned() + self.default_stake_pool.as_str()).as_str());
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Replaces the whole configuration, read it from `config` first to change a single value. Only
+    /// the owner points the contract at another voting contract or default pool.
+    #[payable]
+    pub fn update_config(&mut self, config: WrappedConfig) -> WrappedConfig {
+        self.assert_role(Role::ConfigAdmin);
+        assert_one_yocto();
+
+        let new_config = Config::from(config);
+        new_config.assert_valid();
+        // they hold the motions and the stakes, a config admin only tunes the numbers
+        if new_config.voting_contract != self.config.voting_contract
+            || new_config.default_stake_pool != self.config.default_stake_pool
+        {
+            self.assert_role(Role::Owner);
+        }
+        EventKind::ConfigChanged(vec![ConfigChangedData {
+            old_config: (&self.config).into(),
+            new_config: (&new_config).into(),
+            changed_by: &env::predecessor_account_id(),
+        }])
+        .emit();
+        self.config = new_config;
+
+        self.config()
+    }
+
+    pub fn config(&self) -> WrappedConfig {
+        (&self.config).into()
+    }
+}
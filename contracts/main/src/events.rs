@@ -0,0 +1,259 @@
+use crate::*;
+
+use near_sdk::json_types::{U64, U128};
+use near_sdk::{EpochHeight, Timestamp, serde_json};
+
+const EVENT_STANDARD: &str = "titstake";
+const EVENT_VERSION: &str = "1.0.0";
+
+/// NEP-297 event log, written as `EVENT_JSON:{...}` so indexers can pick it up.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a EventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde", tag = "event", content = "data", rename_all = "snake_case")]
+pub enum EventKind<'a> {
+    BetCreated(Vec<BetCreatedData<'a>>),
+    StakeCreated(Vec<StakeCreatedData<'a>>),
+    StakeFailed(Vec<StakeFailedData<'a>>),
+    StakeMatched(Vec<StakeMatchedData<'a>>),
+    StakeCancelRequested(Vec<StakeCancelRequestedData<'a>>),
+    StakeRefunded(Vec<StakeRefundedData<'a>>),
+    SettlementStarted(Vec<SettlementStartedData<'a>>),
+    BetSettled(Vec<BetSettledData<'a>>),
+    YieldAccrued(Vec<YieldAccruedData<'a>>),
+    KeeperRewarded(Vec<KeeperRewardedData<'a>>),
+    PoolChanged(Vec<PoolChangedData<'a>>),
+    AdminChanged(Vec<AdminChangedData<'a>>),
+    ConfigChanged(Vec<ConfigChangedData<'a>>),
+    RoleGranted(Vec<RoleGrantedData<'a>>),
+    RoleRevoked(Vec<RoleRevokedData<'a>>),
+    Paused(Vec<PausedData<'a>>),
+    Unpaused(Vec<UnpausedData<'a>>),
+    OwnershipProposed(Vec<OwnershipProposedData<'a>>),
+    OwnershipProposalCancelled(Vec<OwnershipProposalCancelledData<'a>>),
+    OwnershipTransferred(Vec<OwnershipTransferredData<'a>>),
+    UpgradeStaged(Vec<UpgradeStagedData<'a>>),
+    UpgradeDeployed(Vec<UpgradeDeployedData<'a>>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BetCreatedData<'a> {
+    pub bet_id: &'a BetId,
+    pub person: &'a Person,
+    pub prediction: &'a Prediction,
+    pub end: Timestamp,
+    pub matching_policy: &'a MatchingPolicy,
+    /// Staker of the stake that opened the bet.
+    pub creator: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeCreatedData<'a> {
+    pub stake_id: &'a StakeId,
+    pub bet_id: &'a BetId,
+    pub staker: &'a AccountId,
+    pub position: &'a Position,
+    pub amount: U128,
+    pub odds: Odds,
+    pub gentlemans: bool,
+    pub pool: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeFailedData<'a> {
+    pub stake_id: &'a StakeId,
+    pub bet_id: &'a BetId,
+    pub staker: &'a AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeMatchedData<'a> {
+    pub bet_id: &'a BetId,
+    pub back_stake_id: &'a StakeId,
+    pub lay_stake_id: &'a StakeId,
+    pub backer: &'a AccountId,
+    pub layer: &'a AccountId,
+    /// Backer stake matched.
+    pub amount: U128,
+    /// Layer stake matched against it.
+    pub liability: U128,
+    pub odds: Odds,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeCancelRequestedData<'a> {
+    pub stake_id: &'a StakeId,
+    pub bet_id: &'a BetId,
+    pub staker: &'a AccountId,
+    pub amount: U128,
+    pub pool: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeRefundedData<'a> {
+    pub stake_id: &'a StakeId,
+    pub staker: &'a AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SettlementStartedData<'a> {
+    pub bet_id: &'a BetId,
+    /// None when the bet is voided.
+    pub winner: &'a Option<Position>,
+    /// Principal and yield unstaked per pool.
+    pub pools: Vec<(&'a AccountId, U128)>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BetSettledData<'a> {
+    pub bet_id: &'a BetId,
+    pub winner: &'a Option<Position>,
+    pub payouts: Vec<PayoutData>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutData {
+    pub stake_id: StakeId,
+    pub staker: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct YieldAccruedData<'a> {
+    pub pool: &'a AccountId,
+    /// Growth attributed to the stakes held in the pool.
+    pub amount: U128,
+    pub total_balance: U128,
+    pub epoch: EpochHeight,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeeperRewardedData<'a> {
+    pub keeper: &'a AccountId,
+    pub bet_ids: &'a [BetId],
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolChangedData<'a> {
+    pub old_pool: Option<&'a AccountId>,
+    pub new_pool: &'a AccountId,
+    pub changed_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AdminChangedData<'a> {
+    pub old_admins: &'a [AccountId],
+    pub new_admins: &'a [AccountId],
+    pub changed_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigChangedData<'a> {
+    pub old_config: WrappedConfig,
+    pub new_config: WrappedConfig,
+    pub changed_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleGrantedData<'a> {
+    pub account_id: &'a AccountId,
+    pub role: Role,
+    pub granted_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleRevokedData<'a> {
+    pub account_id: &'a AccountId,
+    pub role: Role,
+    pub revoked_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PausedData<'a> {
+    pub method: Pausable,
+    pub paused_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UnpausedData<'a> {
+    pub method: Pausable,
+    /// The contract itself when an unpause motion lifted it.
+    pub unpaused_by: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipProposedData<'a> {
+    pub owner_id: &'a AccountId,
+    pub proposed_owner_id: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipProposalCancelledData<'a> {
+    pub owner_id: &'a AccountId,
+    pub proposed_owner_id: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipTransferredData<'a> {
+    pub old_owner_id: &'a AccountId,
+    pub new_owner_id: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpgradeStagedData<'a> {
+    /// Hex sha256 of the code.
+    pub hash: &'a str,
+    pub staged_by: &'a AccountId,
+    pub size: U64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpgradeDeployedData<'a> {
+    pub hash: &'a str,
+    /// Layout the state was in before the new code migrates it.
+    pub from_state_version: u16,
+}
+
+impl EventKind<'_> {
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        env::log_str(&format!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap()));
+    }
+}
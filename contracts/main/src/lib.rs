@@ -1,16 +1,42 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::{Base64VecU8};
 use near_sdk::serde::{Serialize, Deserialize};
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, PanicOnDefault, Promise, StorageUsage,
+    env, near_bindgen, AccountId, Balance, BlockHeight, EpochHeight, PanicOnDefault, Promise, StorageUsage,
 };
 
+pub use crate::bet::consent::*;
+pub use crate::bet::epoch::*;
+pub use crate::bet::gentlemans::*;
 pub use crate::bet::internal::*;
+pub use crate::bet::ledger::*;
+pub use crate::bet::matching::*;
+pub use crate::bet::person::*;
+pub use crate::bet::pool::*;
+pub use crate::bet::prediction::*;
+pub use crate::bet::settlement::*;
+pub use crate::bet::takedown::*;
 pub use crate::bet::*;
+pub use crate::config::*;
+pub use crate::events::*;
+pub use crate::migration::*;
+pub use crate::pause::*;
+pub use crate::roles::*;
+pub use crate::storage::*;
+pub use crate::upgrade::*;
 
 mod bet;
+mod config;
+mod events;
+mod migration;
+mod ownership;
+mod pause;
+mod roles;
+mod storage;
+mod upgrade;
 
+// starting values of `Config`, the admins change them with `update_config`
 #[cfg(not(near_testnet))]
 const DEFAULT_STAKE_POOL: &str = "astro-stakers.poolv1.near";
 #[cfg(not(near_testnet))]
@@ -31,11 +57,38 @@ const VOTING_CONTRACT: &str = "dev-1663982099301-84507361434162";
 
 const ID_LEN: u8 = 21;
 const MIN_STAKE: Balance = 69_000_000_000_000_000_000_000;
-const APY: (u32, u32) = (45, 100);
+const MAX_MIN_DURATION: u64 = 365 * 86_400_000_000_000; // a year
+const MAX_REFUND_DELAY_EPOCHS: u8 = 30;
+/// Share of a stake's yield kept as protocol fees at payout, in basis points.
+const PROTOCOL_FEE_BPS: u16 = 500;
+const MAX_PROTOCOL_FEE_BPS: u16 = 2_000;
+const KEEPER_FEE: Balance = 10_000_000_000_000_000_000_000;
+const MAX_EXPIRED_BETS_PER_CALL: u64 = 20;
+const MAX_VOIDED_BETS_PER_CALL: u64 = 50;
+const MIGRATION_BATCH_SIZE: u64 = 100;
+const MAX_REMATCH_PER_CALL: u64 = 20;
+/// Scale of a pool's yield per unit of principal, see `StakePool.yield_per_share`.
+const YIELD_PER_SHARE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+const SETTLEMENT_BATCH_SIZE: u16 = 50;
+const MAX_SETTLEMENT_BATCH_SIZE: u16 = 200;
+/// Blocks a bet's motion request is left in flight before it is asked for again, about ten minutes.
+const MOTION_REQUEST_TIMEOUT: BlockHeight = 600;
+const MAX_CUP_SIZES: u8 = 10;
+const MIN_CID_LEN: usize = 59;
+const MAX_CID_LEN: usize = 100;
+const MAX_ALIAS_LEN: usize = 64;
+const MAX_REASON_LEN: usize = 280;
+/// Decimal odds are stored as hundredths, 200 is 2.00 (even money).
+const ODDS_PRECISION: Odds = 100;
+const EVEN_ODDS: Odds = 200;
+const MIN_ODDS: Odds = 101;
+const MAX_ODDS: Odds = 100_000;
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Account {
-    earnings: UnorderedMap<StakeId, StakeEarning>
+    earnings: UnorderedMap<StakeId, StakeEarning>,
+    /// Principal of the account's live stakes, what the epoch snapshots record.
+    principal: Balance,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -50,33 +103,108 @@ pub struct ContractMetadata {
     pub reference_hash: Option<Base64VecU8>,
     pub pool: Option<AccountId>,
     pub admins: Option<Vec<AccountId>>,
+    /// Policy new bets match by unless their first stake picks one, FIFO when unset.
+    pub matching_policy: Option<MatchingPolicy>,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     pub owner_id: AccountId,
+    /// Next owner once they accept, see `propose_owner`.
+    pub proposed_owner_id: Option<AccountId>,
     pub accounts: UnorderedMap<AccountId, Account>,
-    pub persons: UnorderedSet<Person>,
+    pub persons: UnorderedMap<Person, PersonRecord>,
+    pub bet_approvals: UnorderedMap<BetId, BetApproval>,
     pub bets: UnorderedMap<BetId, Bet>,
+    /// Bet ids per person, so withdrawing a person never walks every bet.
+    pub bets_by_person: LookupMap<Person, UnorderedSet<BetId>>,
     pub stakes: UnorderedMap<StakeId, Stake>,
-    // pub balances_by_epoch: UnorderedMap<EpochHeight, Vec<(AccountId, Balance)>>,
+    /// Stake ids per bet, unmatched ones are those with something left to match.
+    pub unmatched_backs_by_bet: LookupMap<BetId, UnorderedSet<StakeId>>,
+    pub matched_backs_by_bet: LookupMap<BetId, UnorderedSet<StakeId>>,
+    pub unmatched_lays_by_bet: LookupMap<BetId, UnorderedSet<StakeId>>,
+    pub matched_lays_by_bet: LookupMap<BetId, UnorderedSet<StakeId>>,
+    pub stakes_by_account: LookupMap<AccountId, UnorderedSet<StakeId>>,
+    pub balances_by_epoch: UnorderedMap<EpochHeight, EpochBalances>,
+    /// Each account's principal at the start of every epoch it changed in, see `balance_at_epoch`.
+    pub account_balances: LookupMap<AccountId, Vector<(EpochHeight, Balance)>>,
+    pub matches: Vector<Match>,
+    /// Ids of the matches each stake, bet and staker took part in, see `Contract.matches`.
+    pub matches_by_stake: LookupMap<StakeId, Vec<MatchId>>,
+    pub matches_by_bet: LookupMap<BetId, Vector<MatchId>>,
+    pub matches_by_account: LookupMap<AccountId, Vector<MatchId>>,
+    /// Sequence number the next recorded stake gets.
+    pub next_stake_seq: u64,
     pub cancelled_stakes: UnorderedMap<StakeId, CancelledStake>,
-    pub extra_storage_in_bytes_per_bet: StorageUsage,
+    pub gentlemans_offers: UnorderedMap<StakeId, GentlemansOffer>,
+    /// Block each invite commitment was made in, keyed by the commitment.
+    pub invite_commitments: LookupMap<Vec<u8>, BlockHeight>,
+    pub settlements: UnorderedMap<BetId, Settlement>,
+    /// Where each closed bet's settlement stands, see `settle_bet`.
+    pub settlement_cursors: LookupMap<BetId, SettlementCursor>,
+    pub takedowns: Vector<Takedown>,
+    pub pools: UnorderedMap<AccountId, StakePool>,
+    /// NEP-145 storage ledger, what each account prepaid and holds.
+    pub storage_accounts: LookupMap<AccountId, StorageAccount>,
+    pub storage_bytes_per_registration: StorageUsage,
     pub extra_storage_in_bytes_per_stake: StorageUsage,
     pub metadata: LazyOption<ContractMetadata>,
+    /// Holders of each role but `Role::Owner`, which is `owner_id`.
+    pub roles: LookupMap<Role, UnorderedSet<AccountId>>,
+    pub paused: Vec<Pause>,
+    pub config: Config,
+    /// Collected fees not yet paid to keepers, see `process_expired_bets`.
+    pub protocol_fees: Balance,
+    /// Index into the bets where the next `process_expired_bets` call carries on.
+    pub expired_bets_cursor: u64,
+    pub staged_upgrade: Option<StagedUpgrade>,
+    pub staged_code: LazyOption<Vec<u8>>,
+    /// Records of an older layout still to be carried over, see `migrate_records`.
+    pub migration: Option<MigrationCursor>,
 }
 
 /// Helper structure to for keys of the persistent collections.
+/// NOTE: deployed state keys depend on the order, new keys go last.
 #[derive(BorshSerialize)]
 pub enum StorageKey {
     ContractMetadata,
     Accounts,
-    Earnings,
+    Earnings { account_id: AccountId },
     Persons,
     Bets,
     Stakes,
     StakesCancelled,
+    Settlements,
+    Pools,
+    BalancesByEpoch,
+    Matches,
+    UnmatchedBacksByBet,
+    MatchedBacksByBet,
+    UnmatchedLaysByBet,
+    MatchedLaysByBet,
+    BetStakes { bet_id: BetId, position: Position, matched: bool },
+    StakesByAccount,
+    AccountStakes { account_id: AccountId },
+    GentlemansOffers,
+    BetApprovals,
+    Takedowns,
+    StorageAccounts,
+    StagedCode,
+    Roles,
+    RoleMembers { role: Role },
+    SettlementCursors,
+    SettlementStakes { bet_id: BetId },
+    InviteCommitments,
+    AccountBalances,
+    AccountBalanceEpochs { account_id: AccountId },
+    MatchesByStake,
+    MatchesByBet,
+    BetMatches { bet_id: BetId },
+    MatchesByAccount,
+    AccountMatches { account_id: AccountId },
+    BetsByPerson,
+    PersonBets { person: Person },
 }
 
 #[near_bindgen]
@@ -85,31 +213,112 @@ impl Contract {
     pub fn new(owner_id: AccountId, metadata: ContractMetadata) -> Self {
         let mut this = Self {
             owner_id: owner_id.into(),
+            proposed_owner_id: None,
             accounts: UnorderedMap::new(
                 StorageKey::Accounts.try_to_vec().unwrap()
             ),
-            persons: UnorderedSet::new(
+            persons: UnorderedMap::new(
                 StorageKey::Persons.try_to_vec().unwrap()
             ),
-            extra_storage_in_bytes_per_bet: 0,
+            bet_approvals: UnorderedMap::new(
+                StorageKey::BetApprovals.try_to_vec().unwrap()
+            ),
+            storage_accounts: LookupMap::new(
+                StorageKey::StorageAccounts.try_to_vec().unwrap()
+            ),
+            storage_bytes_per_registration: 0,
             extra_storage_in_bytes_per_stake: 0,
             metadata: LazyOption::new(
                 StorageKey::ContractMetadata.try_to_vec().unwrap(),
                 Some(&metadata),
             ),
+            config: Config::default(),
+            protocol_fees: 0,
+            expired_bets_cursor: 0,
+            roles: LookupMap::new(
+                StorageKey::Roles.try_to_vec().unwrap()
+            ),
+            paused: vec![],
             bets: UnorderedMap::new(
                 StorageKey::Bets.try_to_vec().unwrap()
             ),
             stakes: UnorderedMap::new(
                 StorageKey::Stakes.try_to_vec().unwrap()
             ),
+            unmatched_backs_by_bet: LookupMap::new(
+                StorageKey::UnmatchedBacksByBet.try_to_vec().unwrap()
+            ),
+            matched_backs_by_bet: LookupMap::new(
+                StorageKey::MatchedBacksByBet.try_to_vec().unwrap()
+            ),
+            unmatched_lays_by_bet: LookupMap::new(
+                StorageKey::UnmatchedLaysByBet.try_to_vec().unwrap()
+            ),
+            matched_lays_by_bet: LookupMap::new(
+                StorageKey::MatchedLaysByBet.try_to_vec().unwrap()
+            ),
+            bets_by_person: LookupMap::new(
+                StorageKey::BetsByPerson.try_to_vec().unwrap()
+            ),
+            stakes_by_account: LookupMap::new(
+                StorageKey::StakesByAccount.try_to_vec().unwrap()
+            ),
             cancelled_stakes: UnorderedMap::new(
                 StorageKey::StakesCancelled.try_to_vec().unwrap()
             ),
+            gentlemans_offers: UnorderedMap::new(
+                StorageKey::GentlemansOffers.try_to_vec().unwrap()
+            ),
+            invite_commitments: LookupMap::new(
+                StorageKey::InviteCommitments.try_to_vec().unwrap()
+            ),
+            settlements: UnorderedMap::new(
+                StorageKey::Settlements.try_to_vec().unwrap()
+            ),
+            settlement_cursors: LookupMap::new(
+                StorageKey::SettlementCursors.try_to_vec().unwrap()
+            ),
+            takedowns: Vector::new(
+                StorageKey::Takedowns.try_to_vec().unwrap()
+            ),
+            pools: UnorderedMap::new(
+                StorageKey::Pools.try_to_vec().unwrap()
+            ),
+            balances_by_epoch: UnorderedMap::new(
+                StorageKey::BalancesByEpoch.try_to_vec().unwrap()
+            ),
+            account_balances: LookupMap::new(
+                StorageKey::AccountBalances.try_to_vec().unwrap()
+            ),
+            matches: Vector::new(
+                StorageKey::Matches.try_to_vec().unwrap()
+            ),
+            matches_by_stake: LookupMap::new(
+                StorageKey::MatchesByStake.try_to_vec().unwrap()
+            ),
+            matches_by_bet: LookupMap::new(
+                StorageKey::MatchesByBet.try_to_vec().unwrap()
+            ),
+            matches_by_account: LookupMap::new(
+                StorageKey::MatchesByAccount.try_to_vec().unwrap()
+            ),
+            next_stake_seq: 0,
+            staged_upgrade: None,
+            staged_code: LazyOption::new(
+                StorageKey::StagedCode.try_to_vec().unwrap(),
+                None,
+            ),
+            migration: None,
         };
 
-        this.measure_max_storage_cost_per_bet();
+        for admin_id in metadata.admins.unwrap_or_default() {
+            for role in ADMIN_ROLES {
+                this.internal_grant_role(&admin_id, role);
+            }
+        }
+        this.measure_storage_cost_per_registration();
         this.measure_max_storage_cost_per_stake();
+        write_state_version();
 
         this
     }
@@ -125,27 +334,21 @@ impl Contract {
         } // no-production
     } // no-production
 
-    fn measure_max_storage_cost_per_bet(&mut self) {
+    fn measure_storage_cost_per_registration(&mut self) {
         let initial_storage_usage = env::storage_usage();
-        let tmp_bet_id = "a".repeat(21).parse().unwrap();
-        let person: Person = "a".repeat(32).parse().unwrap(); // cid length to persons photo(s) and/or other identifying content
-        let tmp_bet = Bet {
-            prediction: Prediction::Reduction,
-            person: person.clone(),
-            end: env::block_timestamp() + env::block_height(),
-            motion_id: None,
-        };
-        self.persons.insert(&person);
-        self.bets.insert(&tmp_bet_id, &tmp_bet);
-        self.extra_storage_in_bytes_per_bet = env::storage_usage() - initial_storage_usage;
-        self.bets.remove(&tmp_bet_id);
-        self.persons.remove(&person);
+        let tmp_account_id: AccountId = "a".repeat(64).parse().unwrap();
+        self.storage_accounts.insert(&tmp_account_id, &StorageAccount {
+            deposit: 0,
+            used: 0,
+        });
+        self.storage_bytes_per_registration = env::storage_usage() - initial_storage_usage;
+        self.storage_accounts.remove(&tmp_account_id);
     }
 
     fn measure_max_storage_cost_per_stake(&mut self) {
         let initial_storage_usage = env::storage_usage();
         let tmp_bet_id = "a".repeat(21).parse().unwrap();
-        let tmp_stake_id = "a".repeat(21).parse().unwrap();
+        let tmp_stake_id: StakeId = "a".repeat(21).parse().unwrap();
         let tmp_account_id: AccountId = "a".repeat(64).parse().unwrap();
         let tmp_stake = &Stake {
             bet_id: tmp_bet_id,
@@ -155,15 +358,52 @@ impl Contract {
             unmatched: MIN_STAKE * 1000,
             gentlemans: false,
             epoch: env::epoch_height(),
+            settled: false,
+            pool: tmp_account_id.clone(),
+            odds: MAX_ODDS,
+            seq: u64::MAX,
+            accepts: Some(tmp_stake_id.clone()),
+            storage_used: u64::MAX,
         };
         self.accounts.insert(&tmp_account_id.clone(), &Account {
             earnings: UnorderedMap::new(
-                StorageKey::Earnings.try_to_vec().unwrap()
-            )
+                StorageKey::Earnings { account_id: tmp_account_id.clone() }.try_to_vec().unwrap()
+            ),
+            principal: MIN_STAKE * 1000,
         });
+        let mut account = self.accounts.get(&tmp_account_id).unwrap();
+        account.earnings.insert(&tmp_stake_id, &StakeEarning {
+            epochs: (EpochHeight::MAX, EpochHeight::MAX),
+            yield_balance: u128::MAX,
+            yield_per_share: Some(u128::MAX),
+        });
+        self.internal_record_balance(&tmp_account_id, 0);
         self.stakes.insert(&tmp_stake_id, tmp_stake);
+        self.internal_index_stake(&tmp_stake_id, tmp_stake);
+        self.internal_record_match(&Match {
+            bet_id: tmp_stake_id.clone(),
+            back_stake_id: tmp_stake_id.clone(),
+            lay_stake_id: tmp_stake_id.clone(),
+            amount: MIN_STAKE * 1000,
+            liability: MIN_STAKE * 1000,
+            odds: MAX_ODDS,
+            epoch: env::epoch_height(),
+        }, &tmp_account_id, &tmp_account_id);
         self.extra_storage_in_bytes_per_stake = env::storage_usage() - initial_storage_usage;
+        self.matches.pop();
+        self.matches_by_stake.remove(&tmp_stake_id);
+        if let Some(mut bet_matches) = self.matches_by_bet.remove(&tmp_stake_id) {
+            bet_matches.clear();
+        }
+        if let Some(mut account_matches) = self.matches_by_account.remove(&tmp_account_id) {
+            account_matches.clear();
+        }
+        self.internal_unindex_stake(&tmp_stake_id, tmp_stake);
         self.stakes.remove(&tmp_stake_id);
+        account.earnings.remove(&tmp_stake_id);
         self.accounts.remove(&tmp_account_id);
+        if let Some(mut balances) = self.account_balances.remove(&tmp_account_id) {
+            balances.clear();
+        }
     }
 }
@@ -0,0 +1,667 @@
+//! Versioned contract state. The layout version is kept under its own key because the first
+//! deployed layout carried none, `migrate` brings an older layout up to date one version at a time.
+//! Version 2 has not been released yet: it is the layout the contract is heading for, and every
+//! change to `Contract` before its release lands in it rather than in a version 3.
+use crate::*;
+
+use near_sdk::{EpochHeight, Timestamp};
+
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+/// Layout `Contract` is stored in. Bump it with the first layout change after a release and add
+/// the step up from the previous layout to `migrate`.
+pub const STATE_VERSION: u16 = 2;
+
+/// Layout deployed before versioning (version 1).
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub owner_id: AccountId,
+    pub accounts: UnorderedMap<AccountId, AccountV1>,
+    pub persons: UnorderedSet<Person>,
+    pub bets: UnorderedMap<BetId, BetV1>,
+    pub stakes: UnorderedMap<StakeId, StakeV1>,
+    pub cancelled_stakes: UnorderedMap<StakeId, CancelledStakeV1>,
+    pub extra_storage_in_bytes_per_bet: StorageUsage,
+    pub extra_storage_in_bytes_per_stake: StorageUsage,
+    pub metadata: LazyOption<ContractMetadataV1>,
+}
+
+/// An account's earnings map was keyed by the first earning it recorded, so accounts that first
+/// staked in the same epoch shared one map and overwrote each other's entries.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountV1 {
+    pub earnings: UnorderedMap<StakeId, StakeEarningV1>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StakeEarningV1 {
+    pub epochs: (EpochHeight, EpochHeight),
+    pub yield_balance: u128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractMetadataV1 {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+    pub pool: Option<AccountId>,
+    pub admins: Option<Vec<AccountId>>,
+}
+
+/// `Prediction` only had `Reduction` then, which reads the same today.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BetV1 {
+    pub prediction: Prediction,
+    pub person: String,
+    pub end: Timestamp,
+    pub motion_id: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StakeV1 {
+    pub bet_id: BetId,
+    pub position: Position,
+    pub amount: Balance,
+    pub staker: AccountId,
+    pub unmatched: Balance,
+    pub gentlemans: bool,
+    pub epoch: EpochHeight,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CancelledStakeV1 {
+    pub bet_id: BetId,
+    pub amount: Balance,
+    pub staker: AccountId,
+    pub epoch: EpochHeight,
+    pub cancelled: bool,
+}
+
+/// Where the carrying over of a version 1 state has got to, each step rewrites one of the old
+/// collections in batches of `MIGRATION_BATCH_SIZE`.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MigrationStep {
+    Accounts,
+    Persons,
+    Stakes,
+    CancelledStakes,
+    Bets,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MigrationCursor {
+    pub step: MigrationStep,
+    /// Offset into the collection of the current step.
+    pub index: u64,
+    /// Pool every version 1 stake sits in.
+    pub pool: AccountId,
+    /// The version 1 collections as they were stored, read through these while the new ones are
+    /// written under the same keys.
+    pub accounts: UnorderedMap<AccountId, AccountV1>,
+    pub persons: UnorderedSet<Person>,
+    pub bets: UnorderedMap<BetId, BetV1>,
+    pub stakes: UnorderedMap<StakeId, StakeV1>,
+    pub cancelled_stakes: UnorderedMap<StakeId, CancelledStakeV1>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Upgrades the stored state to the current layout. Only the owner can call it, or the
+    /// contract itself right after deploying new code. A state already in the current layout is
+    /// left as it is, so code that keeps the layout deploys through the same path. Records past
+    /// the first batch are carried over by `migrate_records`.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let version = stored_state_version();
+        if version > STATE_VERSION {
+            env::panic_str(("ERR_MIGRATED state_version: ".to_owned() + &version.to_string()).as_str());
+        }
+        // every layout so far starts with the owner
+        let state = env::storage_read(b"STATE").unwrap_or_else(|| env::panic_str("ERR_NO_STATE"));
+        let owner_id: AccountId = BorshDeserialize::deserialize(&mut &state[..]).unwrap();
+        let predecessor = env::predecessor_account_id();
+        if predecessor != owner_id && predecessor != env::current_account_id() {
+            env::panic_str("ERR_NOT_AUTHORIZED");
+        }
+
+        // each step reads its own layout and hands the next one on
+        let mut this = match version {
+            1 => Contract::from_v1(env::state_read().unwrap()),
+            _ => env::state_read().unwrap(),
+        };
+        write_state_version();
+        // a small state is carried over right away, `migrate_records` pages through the rest
+        this.internal_migrate_records(MIGRATION_BATCH_SIZE);
+
+        this
+    }
+
+    /// Carries the next `limit` records of a version 1 state over, the contract stays paused
+    /// until every record is. Returns whether records are left.
+    pub fn migrate_records(&mut self, limit: Option<u64>) -> bool {
+        self.assert_role(Role::Owner);
+        if self.migration.is_none() {
+            env::panic_str("ERR_MIGRATED");
+        }
+
+        self.internal_migrate_records(limit.unwrap_or(MIGRATION_BATCH_SIZE).min(MIGRATION_BATCH_SIZE))
+    }
+
+    pub fn state_version(&self) -> u16 {
+        stored_state_version()
+    }
+}
+
+impl Contract {
+    /// Switches a version 1 state to the current layout. Collections keep their keys, the records
+    /// are rewritten where they are by `internal_migrate_records` and the persons set becomes a
+    /// map under the same key. Stakes never matched back then, so they come over unmatched at even
+    /// odds in the pool the contract used, cancelled remainders leave them the way `cancel_stake`
+    /// takes them off today. Earnings start over since the shared maps kept nothing reliable,
+    /// their entries are left for `clean`.
+    fn from_v1(old: ContractV1) -> Self {
+        let config = Config::default();
+        let metadata = old.metadata.get().unwrap();
+        let pool = metadata.pool.clone().unwrap_or_else(|| config.default_stake_pool.clone());
+
+        let mut this = Self {
+            owner_id: old.owner_id,
+            proposed_owner_id: None,
+            accounts: retype(&old.accounts),
+            persons: UnorderedMap::new(
+                StorageKey::Persons.try_to_vec().unwrap()
+            ),
+            bet_approvals: UnorderedMap::new(
+                StorageKey::BetApprovals.try_to_vec().unwrap()
+            ),
+            storage_accounts: LookupMap::new(
+                StorageKey::StorageAccounts.try_to_vec().unwrap()
+            ),
+            storage_bytes_per_registration: 0,
+            extra_storage_in_bytes_per_stake: 0,
+            metadata: LazyOption::new(
+                StorageKey::ContractMetadata.try_to_vec().unwrap(),
+                Some(&ContractMetadata {
+                    spec: metadata.spec,
+                    name: metadata.name,
+                    symbol: metadata.symbol,
+                    icon: metadata.icon,
+                    base_uri: metadata.base_uri,
+                    reference: metadata.reference,
+                    reference_hash: metadata.reference_hash,
+                    pool: metadata.pool,
+                    admins: metadata.admins.clone(),
+                    matching_policy: None,
+                }),
+            ),
+            config,
+            protocol_fees: 0,
+            expired_bets_cursor: 0,
+            roles: LookupMap::new(
+                StorageKey::Roles.try_to_vec().unwrap()
+            ),
+            paused: vec![],
+            bets: retype(&old.bets),
+            stakes: retype(&old.stakes),
+            unmatched_backs_by_bet: LookupMap::new(
+                StorageKey::UnmatchedBacksByBet.try_to_vec().unwrap()
+            ),
+            matched_backs_by_bet: LookupMap::new(
+                StorageKey::MatchedBacksByBet.try_to_vec().unwrap()
+            ),
+            unmatched_lays_by_bet: LookupMap::new(
+                StorageKey::UnmatchedLaysByBet.try_to_vec().unwrap()
+            ),
+            matched_lays_by_bet: LookupMap::new(
+                StorageKey::MatchedLaysByBet.try_to_vec().unwrap()
+            ),
+            bets_by_person: LookupMap::new(
+                StorageKey::BetsByPerson.try_to_vec().unwrap()
+            ),
+            stakes_by_account: LookupMap::new(
+                StorageKey::StakesByAccount.try_to_vec().unwrap()
+            ),
+            cancelled_stakes: retype(&old.cancelled_stakes),
+            gentlemans_offers: UnorderedMap::new(
+                StorageKey::GentlemansOffers.try_to_vec().unwrap()
+            ),
+            invite_commitments: LookupMap::new(
+                StorageKey::InviteCommitments.try_to_vec().unwrap()
+            ),
+            settlements: UnorderedMap::new(
+                StorageKey::Settlements.try_to_vec().unwrap()
+            ),
+            settlement_cursors: LookupMap::new(
+                StorageKey::SettlementCursors.try_to_vec().unwrap()
+            ),
+            takedowns: Vector::new(
+                StorageKey::Takedowns.try_to_vec().unwrap()
+            ),
+            pools: UnorderedMap::new(
+                StorageKey::Pools.try_to_vec().unwrap()
+            ),
+            balances_by_epoch: UnorderedMap::new(
+                StorageKey::BalancesByEpoch.try_to_vec().unwrap()
+            ),
+            account_balances: LookupMap::new(
+                StorageKey::AccountBalances.try_to_vec().unwrap()
+            ),
+            matches: Vector::new(
+                StorageKey::Matches.try_to_vec().unwrap()
+            ),
+            matches_by_stake: LookupMap::new(
+                StorageKey::MatchesByStake.try_to_vec().unwrap()
+            ),
+            matches_by_bet: LookupMap::new(
+                StorageKey::MatchesByBet.try_to_vec().unwrap()
+            ),
+            matches_by_account: LookupMap::new(
+                StorageKey::MatchesByAccount.try_to_vec().unwrap()
+            ),
+            next_stake_seq: 0,
+            staged_upgrade: None,
+            staged_code: LazyOption::new(
+                StorageKey::StagedCode.try_to_vec().unwrap(),
+                None,
+            ),
+            migration: None,
+        };
+
+        for admin_id in metadata.admins.unwrap_or_default() {
+            for role in ADMIN_ROLES {
+                this.internal_grant_role(&admin_id, role);
+            }
+        }
+        this.migration = Some(MigrationCursor {
+            step: MigrationStep::Accounts,
+            index: 0,
+            pool,
+            accounts: old.accounts,
+            persons: old.persons,
+            bets: old.bets,
+            stakes: old.stakes,
+            cancelled_stakes: old.cancelled_stakes,
+        });
+
+        this
+    }
+
+    /// Rewrites up to `limit` records of the current step and moves on to the next step once one
+    /// is done. Accounts go first so the stakes can add to them, persons before the bets that
+    /// count on them. Returns whether records are left.
+    pub(crate) fn internal_migrate_records(&mut self, limit: u64) -> bool {
+        let mut cursor = match self.migration.take() {
+            Some(cursor) => cursor,
+            None => return false,
+        };
+        let mut budget = limit;
+        loop {
+            let len = match cursor.step {
+                MigrationStep::Accounts => cursor.accounts.len(),
+                MigrationStep::Persons => cursor.persons.len(),
+                MigrationStep::Stakes => cursor.stakes.len(),
+                MigrationStep::CancelledStakes => cursor.cancelled_stakes.len(),
+                MigrationStep::Bets => cursor.bets.len(),
+            };
+            while cursor.index < len {
+                if budget == 0 {
+                    self.migration = Some(cursor);
+                    return true;
+                }
+                self.internal_migrate_record(&cursor, cursor.index);
+                cursor.index += 1;
+                budget -= 1;
+            }
+            cursor.index = 0;
+            cursor.step = match cursor.step {
+                MigrationStep::Accounts => MigrationStep::Persons,
+                MigrationStep::Persons => MigrationStep::Stakes,
+                MigrationStep::Stakes => MigrationStep::CancelledStakes,
+                MigrationStep::CancelledStakes => MigrationStep::Bets,
+                MigrationStep::Bets => break,
+            };
+        }
+
+        self.next_stake_seq = cursor.stakes.len();
+        self.measure_storage_cost_per_registration();
+        self.measure_max_storage_cost_per_stake();
+
+        false
+    }
+
+    fn internal_migrate_record(&mut self, cursor: &MigrationCursor, index: u64) {
+        match cursor.step {
+            MigrationStep::Accounts => {
+                let account_id = cursor.accounts.keys_as_vector().get(index).unwrap();
+                write_map_value(StorageKey::Accounts, index, &Account {
+                    earnings: UnorderedMap::new(
+                        StorageKey::Earnings { account_id }.try_to_vec().unwrap()
+                    ),
+                    principal: 0,
+                });
+            },
+            MigrationStep::Persons => {
+                // the set's entries give way to the map's, the person keeps its place
+                let person = cursor.persons.as_vector().get(index).unwrap();
+                let prefix = StorageKey::Persons.try_to_vec().unwrap();
+                env::storage_remove(&[&prefix[..], b"i", &person.try_to_vec().unwrap()].concat());
+                env::storage_remove(&[&prefix[..], b"e", &index.to_le_bytes()].concat());
+                self.persons.insert(&person, &PersonRecord {
+                    alias: None,
+                    reference_hash: None,
+                    created: env::block_timestamp(),
+                    creator: self.owner_id.clone(),
+                    status: PersonStatus::Active,
+                    bet_count: 0,
+                    account_id: None,
+                    moderated: false,
+                });
+            },
+            MigrationStep::Stakes => {
+                let stake_id = cursor.stakes.keys_as_vector().get(index).unwrap();
+                let stake = cursor.stakes.values_as_vector().get(index).unwrap();
+                let cancelled = cursor.cancelled_stakes
+                    .get(&stake_id)
+                    .map_or(0, |cancelled_stake| cancelled_stake.amount);
+                let stake = Stake {
+                    bet_id: stake.bet_id,
+                    position: stake.position,
+                    amount: stake.amount - cancelled,
+                    staker: stake.staker,
+                    unmatched: stake.unmatched - cancelled,
+                    gentlemans: stake.gentlemans,
+                    epoch: stake.epoch,
+                    settled: false,
+                    pool: cursor.pool.clone(),
+                    odds: EVEN_ODDS,
+                    seq: index,
+                    accepts: None,
+                    storage_used: 0,
+                };
+                write_map_value(StorageKey::Stakes, index, &stake);
+                self.internal_index_stake(&stake_id, &stake);
+
+                let mut account = self.accounts.get(&stake.staker).unwrap_or_else(|| Account {
+                    earnings: UnorderedMap::new(
+                        StorageKey::Earnings { account_id: stake.staker.clone() }.try_to_vec().unwrap()
+                    ),
+                    principal: 0,
+                });
+                account.earnings.insert(&stake_id, &StakeEarning {
+                    epochs: (stake.epoch, stake.epoch),
+                    yield_balance: 0,
+                    yield_per_share: Some(0),
+                });
+                account.principal += stake.amount;
+                self.accounts.insert(&stake.staker, &account);
+                let mut stake_pool = self.internal_migrated_pool(&cursor.pool);
+                stake_pool.staked += stake.amount;
+                stake_pool.shares += stake.amount;
+                self.pools.insert(&cursor.pool, &stake_pool);
+            },
+            MigrationStep::CancelledStakes => {
+                let cancelled_stake = cursor.cancelled_stakes.values_as_vector().get(index).unwrap();
+                // the old refund never recorded itself, an unrefunded remainder sits unstaked
+                let status = if cancelled_stake.cancelled {
+                    CancelStatus::Refunded
+                } else {
+                    let mut stake_pool = self.internal_migrated_pool(&cursor.pool);
+                    stake_pool.unstaked += cancelled_stake.amount;
+                    self.pools.insert(&cursor.pool, &stake_pool);
+                    CancelStatus::Unstaked
+                };
+                write_map_value(StorageKey::StakesCancelled, index, &CancelledStake {
+                    bet_id: cancelled_stake.bet_id,
+                    amount: cancelled_stake.amount,
+                    staker: cancelled_stake.staker,
+                    epoch: cancelled_stake.epoch,
+                    status,
+                    pending: false,
+                    pool: cursor.pool.clone(),
+                });
+            },
+            MigrationStep::Bets => {
+                let bet_id = cursor.bets.keys_as_vector().get(index).unwrap();
+                let bet = cursor.bets.values_as_vector().get(index).unwrap();
+                if let Some(mut record) = self.persons.get(&bet.person) {
+                    record.bet_count += 1;
+                    self.persons.insert(&bet.person, &record);
+                }
+                self.internal_index_person_bet(&bet.person, &bet_id);
+                write_map_value(StorageKey::Bets, index, &Bet {
+                    prediction: bet.prediction,
+                    person: bet.person,
+                    end: bet.end,
+                    motion_id: bet.motion_id,
+                    matching_policy: MatchingPolicy::default(),
+                });
+            },
+        }
+    }
+
+    fn internal_migrated_pool(&self, pool: &AccountId) -> StakePool {
+        self.pools.get(pool).unwrap_or(StakePool {
+            epoch: env::epoch_height(),
+            ..StakePool::default()
+        })
+    }
+}
+
+/// Opens a collection under the same keys with other types, for records rewritten in place.
+fn retype<T: BorshSerialize, U: BorshDeserialize>(collection: &T) -> U {
+    U::try_from_slice(&collection.try_to_vec().unwrap()).unwrap()
+}
+
+/// Overwrites the value at `index` of the `UnorderedMap` under `key`, whatever layout it had.
+/// `UnorderedMap` keeps its values in a `Vector` under the map's prefix followed by "v".
+fn write_map_value<V: BorshSerialize>(key: StorageKey, index: u64, value: &V) {
+    let prefix = key.try_to_vec().unwrap();
+    env::storage_write(&[&prefix[..], b"v", &index.to_le_bytes()].concat(), &value.try_to_vec().unwrap());
+}
+
+/// Version of the stored layout, state written before versioning is version 1.
+fn stored_state_version() -> u16 {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|version| u16::try_from_slice(&version).unwrap())
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &STATE_VERSION.try_to_vec().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+    const PERSON: &str = "bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my";
+
+    fn set_context(predecessor: AccountId) {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .predecessor_account_id(predecessor)
+            .epoch_height(10);
+        testing_env!(builder.build());
+    }
+
+    /// A version 1 state as the deployed contract wrote it: a bet with a back and a lay, part of
+    /// the lay cancelled.
+    fn write_v1_state() {
+        let mut state = ContractV1 {
+            owner_id: accounts(0),
+            accounts: UnorderedMap::new(StorageKey::Accounts.try_to_vec().unwrap()),
+            persons: UnorderedSet::new(StorageKey::Persons.try_to_vec().unwrap()),
+            bets: UnorderedMap::new(StorageKey::Bets.try_to_vec().unwrap()),
+            stakes: UnorderedMap::new(StorageKey::Stakes.try_to_vec().unwrap()),
+            cancelled_stakes: UnorderedMap::new(StorageKey::StakesCancelled.try_to_vec().unwrap()),
+            extra_storage_in_bytes_per_bet: 0,
+            extra_storage_in_bytes_per_stake: 0,
+            metadata: LazyOption::new(
+                StorageKey::ContractMetadata.try_to_vec().unwrap(),
+                Some(&ContractMetadataV1 {
+                    spec: "titstake-1.0.0".to_string(),
+                    name: "titstake".to_string(),
+                    symbol: "TITS".to_string(),
+                    icon: None,
+                    base_uri: None,
+                    reference: None,
+                    reference_hash: None,
+                    pool: Some(accounts(5)),
+                    admins: Some(vec![accounts(1)]),
+                }),
+            ),
+        };
+        // the deposit callback keyed a new account's earnings by its first earning
+        let earnings_map = |stake_id: &str, epoch: EpochHeight| {
+            let earning = StakeEarningV1 {
+                epochs: (epoch, epoch),
+                yield_balance: 0,
+            };
+            let mut earnings: UnorderedMap<StakeId, StakeEarningV1> = UnorderedMap::new(earning.try_to_vec().unwrap());
+            earnings.insert(&stake_id.to_string(), &earning);
+            earnings
+        };
+
+        state.persons.insert(&PERSON.to_string());
+        state.bets.insert(&"bet".to_string(), &BetV1 {
+            prediction: Prediction::Reduction,
+            person: PERSON.to_string(),
+            end: 1_000,
+            motion_id: None,
+        });
+        state.stakes.insert(&"back".to_string(), &StakeV1 {
+            bet_id: "bet".to_string(),
+            position: Position::Back,
+            amount: 10 * NEAR,
+            staker: accounts(2),
+            unmatched: 10 * NEAR,
+            gentlemans: false,
+            epoch: 3,
+        });
+        state.stakes.insert(&"lay".to_string(), &StakeV1 {
+            bet_id: "bet".to_string(),
+            position: Position::Lay,
+            amount: 5 * NEAR,
+            staker: accounts(3),
+            unmatched: 5 * NEAR,
+            gentlemans: false,
+            epoch: 4,
+        });
+        state.cancelled_stakes.insert(&"lay".to_string(), &CancelledStakeV1 {
+            bet_id: "bet".to_string(),
+            amount: 2 * NEAR,
+            staker: accounts(3),
+            epoch: 5,
+            cancelled: false,
+        });
+        state.accounts.insert(&accounts(2), &AccountV1 {
+            earnings: earnings_map("back", 3),
+        });
+        state.accounts.insert(&accounts(3), &AccountV1 {
+            earnings: earnings_map("lay", 4),
+        });
+
+        env::state_write(&state);
+    }
+
+    #[test]
+    fn migrates_a_v1_state() {
+        set_context(accounts(0));
+        write_v1_state();
+
+        let contract = Contract::migrate();
+
+        assert_eq!(contract.state_version(), STATE_VERSION);
+        let metadata = contract.metadata();
+        assert_eq!(metadata.pool, Some(accounts(5)));
+        assert_eq!(metadata.admins, Some(vec![accounts(1)]));
+        assert!(contract.has_role(accounts(1), Role::Moderator));
+        assert!(!contract.has_role(accounts(2), Role::Moderator));
+        assert_eq!(metadata.matching_policy, None);
+
+        let bet = contract.bets.get(&"bet".to_string()).unwrap();
+        assert_eq!(bet.prediction, Prediction::Reduction);
+        assert_eq!(bet.matching_policy, MatchingPolicy::Fifo);
+        let person = contract.persons.get(&PERSON.to_string()).unwrap();
+        assert_eq!(person.bet_count, 1);
+        assert_eq!(person.status, PersonStatus::Active);
+
+        let back = contract.stakes.get(&"back".to_string()).unwrap();
+        assert_eq!((back.amount, back.unmatched), (10 * NEAR, 10 * NEAR));
+        assert_eq!((back.pool.clone(), back.odds, back.seq), (accounts(5), EVEN_ODDS, 0));
+        let lay = contract.stakes.get(&"lay".to_string()).unwrap();
+        assert_eq!((lay.amount, lay.unmatched, lay.seq), (3 * NEAR, 3 * NEAR, 1));
+        assert_eq!(
+            contract.internal_bet_stake_ids(&"bet".to_string(), Some(Position::Back), Some(false)),
+            vec!["back".to_string()]
+        );
+
+        let cancelled_stake = contract.cancelled_stakes.get(&"lay".to_string()).unwrap();
+        assert_eq!(cancelled_stake.status, CancelStatus::Unstaked);
+        assert_eq!(cancelled_stake.pool, accounts(5));
+
+        let account = contract.accounts.get(&accounts(3)).unwrap();
+        assert_eq!(account.principal, 3 * NEAR);
+        assert_eq!(account.earnings.get(&"lay".to_string()).unwrap().epochs, (4, 4));
+        let stake_pool = contract.pools.get(&accounts(5)).unwrap();
+        assert_eq!((stake_pool.staked, stake_pool.unstaked), (13 * NEAR, 2 * NEAR));
+        assert!(contract.extra_storage_in_bytes_per_stake > 0);
+        assert!(contract.storage_bytes_per_registration > 0);
+        assert!(contract.migration.is_none());
+    }
+
+    #[test]
+    fn keeps_a_v2_state_as_it_is() {
+        set_context(accounts(0));
+        let mut contract = Contract::new(accounts(0), ContractMetadata {
+            spec: "titstake-1.0.0".to_string(),
+            name: "titstake".to_string(),
+            symbol: "TITS".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+            pool: Some(accounts(5)),
+            admins: Some(vec![accounts(1)]),
+            matching_policy: Some(MatchingPolicy::ProRata),
+        });
+        contract.protocol_fees = NEAR;
+        contract.expired_bets_cursor = 3;
+        env::state_write(&contract);
+
+        let migrated = Contract::migrate();
+
+        assert_eq!(migrated.state_version(), STATE_VERSION);
+        assert_eq!(migrated.try_to_vec().unwrap(), contract.try_to_vec().unwrap());
+    }
+
+    #[test]
+    fn migrates_a_v1_state_in_batches() {
+        set_context(accounts(0));
+        write_v1_state();
+
+        let mut contract = Contract::from_v1(env::state_read().unwrap());
+        // two accounts, a person, two stakes, a cancelled stake and a bet
+        let mut calls = 1;
+        while contract.internal_migrate_records(1) {
+            assert!(contract.internal_paused(Pausable::NewStake));
+            calls += 1;
+        }
+
+        assert_eq!(calls, 7);
+        assert!(!contract.internal_paused(Pausable::NewStake));
+        assert_eq!(contract.persons.get(&PERSON.to_string()).unwrap().bet_count, 1);
+        assert_eq!(contract.persons.keys().collect::<Vec<_>>(), vec![PERSON.to_string()]);
+        assert_eq!(contract.accounts.get(&accounts(2)).unwrap().principal, 10 * NEAR);
+        assert_eq!(contract.stakes.get(&"lay".to_string()).unwrap().amount, 3 * NEAR);
+        assert_eq!(contract.cancelled_stakes.get(&"lay".to_string()).unwrap().status, CancelStatus::Unstaked);
+        assert_eq!(contract.next_stake_seq, 2);
+    }
+}
@@ -0,0 +1,66 @@
+//! Two-step ownership transfer. The owner proposes the next owner, who takes over by accepting, so
+//! a mistyped account never ends up owning the contract. `Role::Owner` follows `owner_id`.
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Proposes the next owner, replacing any earlier proposal.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner_id: AccountId) -> Option<AccountId> {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        if new_owner_id == self.owner_id {
+            env::panic_str(("ERR_INVALID new_owner_id: ".to_owned() + new_owner_id.as_str()).as_str());
+        }
+
+        EventKind::OwnershipProposed(vec![OwnershipProposedData {
+            owner_id: &self.owner_id,
+            proposed_owner_id: &new_owner_id,
+        }])
+        .emit();
+        self.proposed_owner_id = Some(new_owner_id);
+
+        self.proposed_owner_id.clone()
+    }
+
+    #[payable]
+    pub fn accept_ownership(&mut self) -> AccountId {
+        assert_one_yocto();
+        if self.proposed_owner_id.as_ref() != Some(&env::predecessor_account_id()) {
+            env::panic_str("ERR_NOT_AUTHORIZED proposed_owner_id");
+        }
+
+        EventKind::OwnershipTransferred(vec![OwnershipTransferredData {
+            old_owner_id: &self.owner_id,
+            new_owner_id: &env::predecessor_account_id(),
+        }])
+        .emit();
+        self.owner_id = env::predecessor_account_id();
+        self.proposed_owner_id = None;
+
+        self.owner_id.clone()
+    }
+
+    #[payable]
+    pub fn cancel_ownership_proposal(&mut self) {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        let proposed_owner_id = self.proposed_owner_id.take().unwrap_or_else(|| {
+            env::panic_str("ERR_DOES_NOT_EXIST proposed_owner_id")
+        });
+
+        EventKind::OwnershipProposalCancelled(vec![OwnershipProposalCancelledData {
+            owner_id: &self.owner_id,
+            proposed_owner_id: &proposed_owner_id,
+        }])
+        .emit();
+    }
+
+    pub fn owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    pub fn proposed_owner(&self) -> Option<AccountId> {
+        self.proposed_owner_id.clone()
+    }
+}
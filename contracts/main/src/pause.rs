@@ -0,0 +1,193 @@
+//! Emergency pause. A pauser stops each group of methods on its own, only the owner or an adopted
+//! "unpause" motion starts it again so a single pauser key cannot undo its own stop.
+use crate::*;
+
+use near_sdk::{PromiseResult, Timestamp, serde_json};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Pausable {
+    /// `new_stake` and `accept_gentlemans_offer`, no deposit reaches a pool.
+    NewStake,
+    /// Stakes still land but rest unmatched, accepting a gentleman's offer is refused.
+    Matching,
+    Cancellation,
+    Refunds,
+    /// Starting settlement and every later step of `settle_bet`.
+    Settlement,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Pause {
+    pub method: Pausable,
+    pub paused_by: AccountId,
+    pub paused: Timestamp,
+    /// Pending motion to lift the pause, `enact_unpause` reads it.
+    pub unpause_motion_id: Option<MotionId>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pauses every given group that is not paused yet.
+    #[payable]
+    pub fn pause(&mut self, methods: Vec<Pausable>) -> Vec<Pause> {
+        assert_one_yocto();
+        self.assert_role(Role::Pauser);
+
+        for method in methods {
+            if self.internal_paused(method) {
+                continue;
+            }
+            self.paused.push(Pause {
+                method,
+                paused_by: env::predecessor_account_id(),
+                paused: env::block_timestamp(),
+                unpause_motion_id: None,
+            });
+            EventKind::Paused(vec![PausedData {
+                method,
+                paused_by: &env::predecessor_account_id(),
+            }])
+            .emit();
+        }
+
+        self.paused.clone()
+    }
+
+    #[payable]
+    pub fn unpause(&mut self, methods: Vec<Pausable>) -> Vec<Pause> {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+
+        for method in methods {
+            self.internal_unpause(method);
+        }
+
+        self.paused.clone()
+    }
+
+    /// Puts lifting a pause to the voting pool, `enact_unpause` lifts it once the motion is adopted.
+    pub fn propose_unpause(&mut self, method: Pausable) -> Promise {
+        let pause = self.internal_pause(method);
+        if let Some(motion_id) = &pause.unpause_motion_id {
+            env::panic_str(("ERR_PENDING motion_id: ".to_owned() + motion_id).as_str());
+        }
+
+        ext_voting::ext(self.config.voting_contract.clone())
+            .with_attached_deposit(1)
+            .motions(vec![(
+                Some("unpause".to_string()),
+                format!("unpause {:?}", method),
+                None,
+                None,
+                None,
+            )])
+            .then(ext_self::ext(env::current_account_id()).unpause_motion_callback(method))
+    }
+
+    /// Reads the unpause motion and lifts the pause once it is adopted, a rejected motion can be
+    /// proposed again.
+    pub fn enact_unpause(&mut self, method: Pausable) -> Promise {
+        let pause = self.internal_pause(method);
+        let motion_id = pause.unpause_motion_id.clone().unwrap_or_else(|| {
+            env::panic_str(("ERR_NO_MOTION method: ".to_owned() + &format!("{:?}", method)).as_str())
+        });
+
+        ext_voting::ext(self.config.voting_contract.clone())
+            .view_motions(None, None, Some(vec![motion_id.clone()]), Some("unpause".to_string()))
+            .then(ext_self::ext(env::current_account_id()).unpause_resolve_callback(method, motion_id))
+    }
+
+    pub fn paused(&self) -> Vec<Pause> {
+        self.paused.clone()
+    }
+
+    #[private]
+    pub fn unpause_motion_callback(&mut self, method: Pausable) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                let motions: Vec<Motion> = serde_json::from_slice(&result).unwrap();
+                match self.paused.iter_mut().find(|pause| pause.method == method) {
+                    // unpaused by the owner in the meantime
+                    None => "not paused".to_string(),
+                    Some(pause) => {
+                        pause.unpause_motion_id = motions.into_iter().next().map(|motion| motion.motion_id);
+                        "ok".to_string()
+                    },
+                }
+            },
+        }
+    }
+
+    #[private]
+    pub fn unpause_resolve_callback(&mut self, method: Pausable, motion_id: MotionId) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                // lifted and paused again in the meantime, the old motion no longer speaks for it
+                if !self.paused.iter().any(|pause| pause.method == method && pause.unpause_motion_id.as_ref() == Some(&motion_id)) {
+                    return "replaced".to_string();
+                }
+                let motions: Option<Vec<JsonMotion>> = serde_json::from_slice(&result).unwrap();
+                let motion = motions.unwrap_or_default().into_iter().next().map(|json_motion| json_motion.motion);
+                match motion.map(|motion| motion.status) {
+                    Some(MotionStatus::Adopted) => {
+                        self.internal_unpause(method);
+                        "ok".to_string()
+                    },
+                    Some(MotionStatus::Rejected) => {
+                        if let Some(pause) = self.paused.iter_mut().find(|pause| pause.method == method) {
+                            pause.unpause_motion_id = None;
+                        }
+                        "rejected".to_string()
+                    },
+                    _ => "not resolved".to_string(),
+                }
+            },
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_not_paused(&self, method: Pausable) {
+        if self.migration.is_some() {
+            env::panic_str("ERR_MIGRATING");
+        }
+        if self.internal_paused(method) {
+            env::panic_str(("ERR_PAUSED method: ".to_owned() + &format!("{:?}", method)).as_str());
+        }
+    }
+
+    /// Whether the group is paused, everything is while a migration carries records over.
+    pub(crate) fn internal_paused(&self, method: Pausable) -> bool {
+        self.migration.is_some() || self.paused.iter().any(|pause| pause.method == method)
+    }
+
+    fn internal_pause(&self, method: Pausable) -> &Pause {
+        self.paused.iter().find(|pause| pause.method == method).unwrap_or_else(|| {
+            env::panic_str(("ERR_NOT_PAUSED method: ".to_owned() + &format!("{:?}", method)).as_str())
+        })
+    }
+
+    fn internal_unpause(&mut self, method: Pausable) {
+        if !self.internal_paused(method) {
+            return;
+        }
+        self.paused.retain(|pause| pause.method != method);
+        EventKind::Unpaused(vec![UnpausedData {
+            method,
+            unpaused_by: &env::predecessor_account_id(),
+        }])
+        .emit();
+    }
+}
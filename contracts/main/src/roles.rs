@@ -0,0 +1,127 @@
+//! Role-based access. Each privileged method asks for one role, the owner holds every role and is
+//! the only one who grants or revokes them.
+use crate::*;
+
+use near_sdk::json_types::U128;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// Held by `owner_id` alone, it moves with ownership rather than through grants.
+    Owner,
+    ConfigAdmin,
+    PoolManager,
+    Moderator,
+    Pauser,
+    Keeper,
+}
+
+/// Roles the accounts in `ContractMetadata.admins` hold, `change_admin_users` keeps them in step.
+pub const ADMIN_ROLES: [Role; 3] = [Role::ConfigAdmin, Role::PoolManager, Role::Moderator];
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) -> bool {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        self.internal_grant_role(&account_id, role)
+    }
+
+    #[payable]
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) -> bool {
+        assert_one_yocto();
+        self.assert_role(Role::Owner);
+        self.internal_revoke_role(&account_id, role)
+    }
+
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        self.internal_has_role(&account_id, role)
+    }
+
+    pub fn roles_of(&self, account_id: AccountId) -> Vec<Role> {
+        [Role::Owner, Role::ConfigAdmin, Role::PoolManager, Role::Moderator, Role::Pauser, Role::Keeper]
+            .iter()
+            .copied()
+            .filter(|role| self.internal_has_role(&account_id, *role))
+            .collect()
+    }
+
+    pub fn role_members(&self, role: Role, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        if role == Role::Owner {
+            return vec![self.owner_id.clone()];
+        }
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.roles.get(&role).map_or_else(Vec::new, |members| {
+            members
+                .iter()
+                //skip to the index we specified in the start variable
+                .skip(start as usize)
+                //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+                .take(limit.unwrap_or(50) as usize)
+                .collect()
+        })
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_role(&self, role: Role) {
+        if !self.internal_has_role(&env::predecessor_account_id(), role) {
+            env::panic_str(("ERR_NOT_AUTHORIZED role: ".to_owned() + &format!("{:?}", role)).as_str());
+        }
+    }
+
+    pub(crate) fn internal_has_role(&self, account_id: &AccountId, role: Role) -> bool {
+        account_id == &self.owner_id
+            || (role != Role::Owner && self.roles.get(&role).is_some_and(|members| members.contains(account_id)))
+    }
+
+    /// Returns whether the account did not hold the role yet.
+    pub(crate) fn internal_grant_role(&mut self, account_id: &AccountId, role: Role) -> bool {
+        if role == Role::Owner {
+            env::panic_str("ERR_INVALID role: Owner");
+        }
+        let mut members = self.roles.get(&role).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::RoleMembers { role }.try_to_vec().unwrap())
+        });
+        let granted = members.insert(account_id);
+        self.roles.insert(&role, &members);
+        if granted {
+            EventKind::RoleGranted(vec![RoleGrantedData {
+                account_id,
+                role,
+                granted_by: &env::predecessor_account_id(),
+            }])
+            .emit();
+        }
+
+        granted
+    }
+
+    /// Returns whether the account held the role.
+    pub(crate) fn internal_revoke_role(&mut self, account_id: &AccountId, role: Role) -> bool {
+        if role == Role::Owner {
+            env::panic_str("ERR_INVALID role: Owner");
+        }
+        let revoked = match self.roles.get(&role) {
+            Some(mut members) => {
+                let revoked = members.remove(account_id);
+                self.roles.insert(&role, &members);
+                revoked
+            },
+            None => false,
+        };
+        if revoked {
+            EventKind::RoleRevoked(vec![RoleRevokedData {
+                account_id,
+                role,
+                revoked_by: &env::predecessor_account_id(),
+            }])
+            .emit();
+        }
+
+        revoked
+    }
+}
@@ -0,0 +1,172 @@
+//! [NEP-145](https://nomicon.io/Standards/StorageManagement) storage management. Every account
+//! prepays the storage its stakes take, a stake reserves it when placed and gives it back once
+//! settled.
+use crate::*;
+
+use near_sdk::json_types::U128;
+
+/// What an account prepaid for storage and how many bytes of it are in use, the registration
+/// itself included.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageAccount {
+    pub deposit: Balance,
+    pub used: StorageUsage,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let amount = env::attached_deposit();
+        let min = self.storage_balance_bounds().min.0;
+
+        if let Some(mut storage_account) = self.storage_accounts.get(&account_id) {
+            if registration_only.unwrap_or(false) {
+                // already registered, nothing to pay for
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                storage_account.deposit += amount;
+                self.storage_accounts.insert(&account_id, &storage_account);
+            }
+        } else {
+            if amount < min {
+                env::panic_str(("ERR_INSF_DEPOSIT min: ".to_owned() + &min.to_string()).as_str());
+            }
+            let deposit = if registration_only.unwrap_or(false) {
+                if amount > min {
+                    Promise::new(env::predecessor_account_id()).transfer(amount - min);
+                }
+                min
+            } else {
+                amount
+            };
+            self.storage_accounts.insert(&account_id, &StorageAccount {
+                deposit,
+                used: self.storage_bytes_per_registration,
+            });
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut storage_account = self.storage_accounts.get(&account_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_NOT_REGISTERED account_id: ".to_owned() + account_id.as_str()).as_str())
+        });
+        let available = storage_available(&storage_account);
+        let amount = amount.map_or(available, |amount| amount.0);
+        if amount > available {
+            env::panic_str(("ERR_INSF_STORAGE available: ".to_owned() + &available.to_string()).as_str());
+        }
+
+        storage_account.deposit -= amount;
+        self.storage_accounts.insert(&account_id, &storage_account);
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Closes an account's storage ledger and returns its deposit. Stakes still live hold storage
+    /// and funds in the pools, so an account with any cannot close, `force` is not supported: it
+    /// would forfeit the stakes.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_account = match self.storage_accounts.get(&account_id) {
+            Some(storage_account) => storage_account,
+            None => return false,
+        };
+        if storage_account.used > self.storage_bytes_per_registration {
+            if force.unwrap_or(false) {
+                env::panic_str("ERR_FORCE_UNSUPPORTED");
+            }
+            env::panic_str(("ERR_STORAGE_IN_USE account_id: ".to_owned() + account_id.as_str()).as_str());
+        }
+
+        self.storage_accounts.remove(&account_id);
+        if storage_account.deposit > 0 {
+            Promise::new(account_id).transfer(storage_account.deposit);
+        }
+
+        true
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts.get(&account_id).map(|storage_account| StorageBalance {
+            total: U128(storage_account.deposit),
+            available: U128(storage_available(&storage_account)),
+        })
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(Balance::from(self.storage_bytes_per_registration) * env::storage_byte_cost()),
+            max: None,
+        }
+    }
+}
+
+impl Contract {
+    /// Takes `bytes` out of what the account prepaid, panics if it did not prepay enough.
+    pub(crate) fn internal_reserve_storage(&mut self, account_id: &AccountId, bytes: StorageUsage) {
+        let mut storage_account = self.storage_accounts.get(account_id).unwrap_or_else(|| {
+            env::panic_str(("ERR_NOT_REGISTERED account_id: ".to_owned() + account_id.as_str()).as_str())
+        });
+        let required = Balance::from(bytes) * env::storage_byte_cost();
+        let available = storage_available(&storage_account);
+        if required > available {
+            env::panic_str(("ERR_INSF_STORAGE required: ".to_owned() + &required.to_string() + " available: " + &available.to_string()).as_str());
+        }
+
+        storage_account.used += bytes;
+        self.storage_accounts.insert(account_id, &storage_account);
+    }
+
+    /// Moves an account's storage use from what was reserved to what was written. Runs in callbacks
+    /// and on settlement where panicking is no option, so an account can end up owing a few bytes
+    /// and then cannot reserve more until it tops up.
+    pub(crate) fn internal_settle_storage(
+        &mut self,
+        account_id: &AccountId,
+        reserved: StorageUsage,
+        used: StorageUsage,
+    ) {
+        if let Some(mut storage_account) = self.storage_accounts.get(account_id) {
+            storage_account.used = (storage_account.used + used)
+                .saturating_sub(reserved)
+                .max(self.storage_bytes_per_registration);
+            self.storage_accounts.insert(account_id, &storage_account);
+        }
+    }
+}
+
+fn storage_available(storage_account: &StorageAccount) -> Balance {
+    storage_account.deposit.saturating_sub(Balance::from(storage_account.used) * env::storage_byte_cost())
+}
@@ -0,0 +1,183 @@
+//! Self-upgrade approved by governance. The owner stages new code, which puts an "upgrade" motion
+//! naming its hash to the voting pool, and anyone can deploy it once that motion is adopted.
+use crate::*;
+
+use near_sdk::json_types::U64;
+use near_sdk::{Gas, PromiseResult, Timestamp, serde_json};
+
+const GAS_FOR_MIGRATE: Gas = Gas(50 * Gas::ONE_TERA.0);
+
+/// Code waiting on its motion, the code itself sits in `Contract.staged_code`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StagedUpgrade {
+    /// Hex sha256 of the code, the motion has to name it.
+    pub hash: String,
+    pub staged_by: AccountId,
+    pub staged: Timestamp,
+    pub motion_id: Option<MotionId>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedStagedUpgrade {
+    pub hash: String,
+    pub staged_by: AccountId,
+    pub staged: Timestamp,
+    pub motion_id: Option<MotionId>,
+    pub size: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Stores new code and puts it to the voting pool, replacing whatever was staged before. The
+    /// attached deposit pays for the code.
+    #[payable]
+    pub fn stage_upgrade(&mut self, code: Base64VecU8) -> Promise {
+        self.assert_role(Role::Owner);
+        if code.0.is_empty() {
+            env::panic_str("ERR_INVALID code");
+        }
+        let initial_storage_usage = env::storage_usage();
+        let hash = hex(&env::sha256(&code.0));
+        self.staged_code.set(&code.0);
+        self.staged_upgrade = Some(StagedUpgrade {
+            hash: hash.clone(),
+            staged_by: env::predecessor_account_id(),
+            staged: env::block_timestamp(),
+            motion_id: None,
+        });
+        internal::refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        EventKind::UpgradeStaged(vec![UpgradeStagedData {
+            hash: &hash,
+            staged_by: &env::predecessor_account_id(),
+            size: U64(code.0.len() as u64),
+        }])
+        .emit();
+
+        ext_voting::ext(self.config.voting_contract.clone())
+            .with_attached_deposit(1)
+            .motions(vec![(
+                Some("upgrade".to_string()),
+                format!("upgrade {}", hash),
+                None,
+                None,
+                None,
+            )])
+            .then(ext_self::ext(env::current_account_id()).upgrade_motion_callback(hash))
+    }
+
+    /// Reads the motion of the staged code and, once it is adopted, deploys the code and migrates
+    /// the state in the same batch. The code is dropped once that batch went through, or right away
+    /// when the motion is rejected.
+    pub fn deploy_upgrade(&mut self) -> Promise {
+        let staged_upgrade = self.staged_upgrade.as_ref().unwrap_or_else(|| {
+            env::panic_str("ERR_DOES_NOT_EXIST upgrade")
+        });
+        let motion_id = staged_upgrade.motion_id.clone().unwrap_or_else(|| {
+            env::panic_str(("ERR_NO_MOTION hash: ".to_owned() + &staged_upgrade.hash).as_str())
+        });
+
+        ext_voting::ext(self.config.voting_contract.clone())
+            .view_motions(None, None, Some(vec![motion_id]), Some("upgrade".to_string()))
+            .then(ext_self::ext(env::current_account_id()).upgrade_resolve_callback(staged_upgrade.hash.clone()))
+    }
+
+    pub fn staged_upgrade(&self) -> Option<WrappedStagedUpgrade> {
+        self.staged_upgrade.as_ref().map(|staged_upgrade| WrappedStagedUpgrade {
+            hash: staged_upgrade.hash.clone(),
+            staged_by: staged_upgrade.staged_by.clone(),
+            staged: staged_upgrade.staged,
+            motion_id: staged_upgrade.motion_id.clone(),
+            size: U64(self.staged_code.get().map_or(0, |code| code.len() as u64)),
+        })
+    }
+
+    pub fn upgrade_motion_callback(&mut self, hash: String) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                let motions: Vec<Motion> = serde_json::from_slice(&result).unwrap();
+                match self.staged_upgrade.as_mut() {
+                    // staged again in the meantime, that call records its own motion
+                    Some(staged_upgrade) if staged_upgrade.hash == hash => {
+                        staged_upgrade.motion_id = motions.into_iter().next().map(|motion| motion.motion_id);
+                        "ok".to_string()
+                    },
+                    _ => "replaced".to_string(),
+                }
+            },
+        }
+    }
+
+    #[private]
+    pub fn upgrade_resolve_callback(&mut self, hash: String) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(result) => {
+                if !matches!(&self.staged_upgrade, Some(staged_upgrade) if staged_upgrade.hash == hash) {
+                    return "replaced".to_string();
+                }
+                let motions: Option<Vec<JsonMotion>> = serde_json::from_slice(&result).unwrap();
+                let motion = motions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|json_motion| json_motion.motion)
+                    .find(|motion| motion.category.as_deref() == Some("upgrade") && motion.description.contains(&hash));
+                match motion.map(|motion| motion.status) {
+                    Some(MotionStatus::Adopted) => {
+                        let code = self.staged_code.get().unwrap();
+                        EventKind::UpgradeDeployed(vec![UpgradeDeployedData {
+                            hash: &hash,
+                            from_state_version: self.state_version(),
+                        }])
+                        .emit();
+                        // a failing migrate reverts the deploy with it, the code stays staged to deploy again
+                        Promise::new(env::current_account_id())
+                            .deploy_contract(code)
+                            .function_call("migrate".to_string(), vec![], 0, GAS_FOR_MIGRATE)
+                            .then(ext_self::ext(env::current_account_id()).upgrade_deployed_callback(hash));
+                        "deploying...".to_string()
+                    },
+                    Some(MotionStatus::Rejected) => {
+                        self.staged_code.remove();
+                        self.staged_upgrade = None;
+                        "rejected".to_string()
+                    },
+                    _ => "not resolved".to_string(),
+                }
+            },
+        }
+    }
+
+    /// Runs on the new code once it is deployed and migrated, only then is the staged code dropped.
+    #[private]
+    pub fn upgrade_deployed_callback(&mut self, hash: String) -> String {
+        assert_callback();
+
+        // handle the result from the cross contract call this method is a callback for
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Failed => "oops!".to_string(),
+            PromiseResult::Successful(_) => {
+                if !matches!(&self.staged_upgrade, Some(staged_upgrade) if staged_upgrade.hash == hash) {
+                    return "replaced".to_string();
+                }
+                self.staged_code.remove();
+                self.staged_upgrade = None;
+                "ok".to_string()
+            },
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,64 @@
+use crate::*;
+
+use near_sdk::serde_json;
+
+const EVENT_STANDARD: &str = "titstake-voting";
+const EVENT_VERSION: &str = "1.0.0";
+
+/// NEP-297 event log, written as `EVENT_JSON:{...}` so indexers can pick it up.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a EventKind<'a>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde", tag = "event", content = "data", rename_all = "snake_case")]
+pub enum EventKind<'a> {
+    OwnershipProposed(Vec<OwnershipProposedData<'a>>),
+    OwnershipProposalCancelled(Vec<OwnershipProposalCancelledData<'a>>),
+    OwnershipTransferred(Vec<OwnershipTransferredData<'a>>),
+    MotionCallerAdded(Vec<MotionCallerData<'a>>),
+    MotionCallerRemoved(Vec<MotionCallerData<'a>>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipProposedData<'a> {
+    pub owner_id: &'a AccountId,
+    pub proposed_owner_id: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipProposalCancelledData<'a> {
+    pub owner_id: &'a AccountId,
+    pub proposed_owner_id: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipTransferredData<'a> {
+    pub old_owner_id: &'a AccountId,
+    pub new_owner_id: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MotionCallerData<'a> {
+    pub account_id: &'a AccountId,
+}
+
+impl EventKind<'_> {
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        log!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap());
+    }
+}
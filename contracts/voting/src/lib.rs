@@ -6,12 +6,17 @@ use near_sdk::{
 };
 
 use crate::internal::*;
+pub use crate::events::*;
+pub use crate::migration::*;
 pub use crate::motion::*;
 pub use crate::registration::*;
 pub use crate::vote::*;
 
+mod events;
 mod internal;
+mod migration;
 mod motion;
+mod ownership;
 mod registration;
 mod vote;
 
@@ -28,9 +33,13 @@ near_sdk::setup_alloc!();
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     pub owner_id: AccountId,
+    /// Next owner once they accept, see `propose_owner`.
+    pub proposed_owner_id: Option<AccountId>,
     pub voters: Vec<Voter>,
     pub motions: UnorderedSet<Motion>,
     pub voters_per_motion: LookupMap<MotionId, UnorderedSet<Voter>>,
+    /// Contracts allowed to put motions up without a voter seat, see `add_motion_caller`.
+    pub motion_callers: Vec<AccountId>,
 
     /// The storage size in bytes for one motion.
     pub extra_storage_in_bytes_per_motion: StorageUsage,
@@ -51,13 +60,16 @@ impl Contract {
     pub fn new(owner_id: AccountId) -> Self {
         let mut this = Self {
             owner_id,
+            proposed_owner_id: None,
             voters: vec![],
             motions: UnorderedSet::new(StorageKey::Motions.try_to_vec().unwrap()),
             voters_per_motion: LookupMap::new(StorageKey::VotersPerMotion.try_to_vec().unwrap()),
+            motion_callers: vec![],
             extra_storage_in_bytes_per_motion: 0,
         };
 
         this.measure_typical_motion_storage_cost();
+        write_state_version();
 
         this
     }
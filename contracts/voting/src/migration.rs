@@ -0,0 +1,154 @@
+//! Versioned contract state. The layout version is kept under its own key because the first
+//! deployed layout carried none, `migrate` brings an older layout up to date one version at a time.
+//! Version 2 has not been released yet: it is the layout the contract is heading for, and every
+//! change to `Contract` before its release lands in it rather than in a version 3.
+use crate::*;
+
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+/// Layout `Contract` is stored in. Bump it with the first layout change after a release and add
+/// the step up from the previous layout to `migrate`.
+pub const STATE_VERSION: u16 = 2;
+
+/// Layout before versioning, the first deployed one.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub owner_id: AccountId,
+    pub voters: Vec<Voter>,
+    pub motions: UnorderedSet<Motion>,
+    pub voters_per_motion: LookupMap<MotionId, UnorderedSet<Voter>>,
+    pub extra_storage_in_bytes_per_motion: StorageUsage,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Brings the stored state up to the current layout. Only the owner can call it, or the
+    /// contract itself right after deploying new code.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let version = stored_state_version();
+        assert!(
+            !matches!(version, Some(version) if version >= STATE_VERSION),
+            "State is already at version {}.",
+            STATE_VERSION,
+        );
+        // every layout so far starts with the owner
+        let state = env::storage_read(b"STATE").expect("No state to migrate.");
+        let owner_id: AccountId = BorshDeserialize::deserialize(&mut &state[..]).unwrap();
+        let predecessor = env::predecessor_account_id();
+        assert!(
+            predecessor == owner_id || predecessor == env::current_account_id(),
+            "Only the owner can migrate.",
+        );
+
+        // each step reads its own layout and hands the next one on
+        let this: Contract = match version.unwrap_or(1) {
+            1 => Contract::from_v1(env::state_read().unwrap()),
+            _ => unreachable!(),
+        };
+        write_state_version();
+
+        this
+    }
+
+    pub fn state_version(&self) -> u16 {
+        stored_state_version().unwrap_or(1)
+    }
+}
+
+impl Contract {
+    /// Version 2 adds the pending owner of a two-step transfer and the motion callers, nothing
+    /// else moves.
+    fn from_v1(old: ContractV1) -> Self {
+        Self {
+            owner_id: old.owner_id,
+            proposed_owner_id: None,
+            voters: old.voters,
+            motions: old.motions,
+            voters_per_motion: old.voters_per_motion,
+            motion_callers: vec![],
+            extra_storage_in_bytes_per_motion: old.extra_storage_in_bytes_per_motion,
+        }
+    }
+}
+
+/// Version of the stored layout, None for state written before versioning.
+fn stored_state_version() -> Option<u16> {
+    env::storage_read(STATE_VERSION_KEY).map(|version| u16::try_from_slice(&version).unwrap())
+}
+
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &STATE_VERSION.try_to_vec().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    #[test]
+    fn round_trips_a_v2_state() {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(accounts(1))
+            .predecessor_account_id(accounts(1));
+        testing_env!(builder.build());
+
+        let mut contract = Contract::new(accounts(1).into());
+        contract.proposed_owner_id = Some(accounts(2).into());
+        contract.motion_callers = vec![accounts(3).into()];
+        env::state_write(&contract);
+
+        let stored: Contract = env::state_read().unwrap();
+
+        assert_eq!(stored.try_to_vec().unwrap(), contract.try_to_vec().unwrap());
+        assert_eq!(stored.state_version(), STATE_VERSION);
+    }
+
+    #[test]
+    fn migrates_an_unversioned_state() {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(accounts(1))
+            .predecessor_account_id(accounts(1));
+        testing_env!(builder.build());
+
+        // state as the contract wrote it before versioning
+        let mut state = ContractV1 {
+            owner_id: accounts(1).into(),
+            voters: vec![Voter {
+                account_id: accounts(1).into(),
+                vote: None,
+                status: VoterStatus::REGISTERED,
+            }],
+            motions: UnorderedSet::new(StorageKey::Motions.try_to_vec().unwrap()),
+            voters_per_motion: LookupMap::new(StorageKey::VotersPerMotion.try_to_vec().unwrap()),
+            extra_storage_in_bytes_per_motion: 100,
+        };
+        state.motions.insert(&Motion {
+            motion_id: "1".to_string(),
+            category: Some("upgrade".to_string()),
+            description: "deploy abc".to_string(),
+            status: MotionStatus::OPEN,
+            registered: vec![accounts(1).into()],
+            log: vec![],
+            quorum: None,
+            end: None,
+        });
+        env::state_write(&state);
+
+        let contract = Contract::migrate();
+
+        assert_eq!(contract.state_version(), STATE_VERSION);
+        assert_eq!(contract.owner_id, accounts(1).to_string());
+        assert_eq!(contract.proposed_owner_id, None);
+        assert!(contract.motion_callers.is_empty());
+        assert_eq!(contract.voters.len(), 1);
+        assert_eq!(contract.extra_storage_in_bytes_per_motion, 100);
+        let motions = contract.motions.to_vec();
+        assert_eq!(motions.len(), 1);
+        assert_eq!(motions[0].category, Some("upgrade".to_string()));
+    }
+}
@@ -55,7 +55,7 @@ impl Contract {
         )>
     ) -> Vec<Motion> {
         assert_one_yocto();
-        assert!(self.is_registered(), "You must be registered.");
+        assert!(self.is_registered() || self.is_motion_caller(), "You must be registered.");
 
         params_vec.iter().map(|params| {
             self.internal_post_assert_motion(
@@ -72,11 +72,46 @@ impl Contract {
     #[payable]
     pub fn motion(&mut self, category: Option<String>, description: String, value: Option<VoteValue>, quorum: Option<u32>, pool: Option<Vec<AccountId>>) -> Motion {
         assert_one_yocto();
-        assert!(self.is_registered(), "You must be registered.");
+        assert!(self.is_registered() || self.is_motion_caller(), "You must be registered.");
 
         self.internal_post_assert_motion(category, description, value, quorum, pool)
     }
     
+    /// Lets a contract put motions up for its users, the main contract asks for a bet's motion
+    /// once the bet ends without being a voter itself.
+    #[payable]
+    pub fn add_motion_caller(&mut self, account_id: AccountId) -> Vec<AccountId> {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(env::is_valid_account_id(account_id.as_bytes()), "Invalid account {}.", account_id);
+        assert!(!self.motion_callers.contains(&account_id), "{} already puts motions up.", account_id);
+
+        EventKind::MotionCallerAdded(vec![MotionCallerData { account_id: &account_id }]).emit();
+        self.motion_callers.push(account_id);
+
+        self.motion_callers.clone()
+    }
+
+    #[payable]
+    pub fn remove_motion_caller(&mut self, account_id: AccountId) -> Vec<AccountId> {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(self.motion_callers.contains(&account_id), "{} does not put motions up.", account_id);
+
+        EventKind::MotionCallerRemoved(vec![MotionCallerData { account_id: &account_id }]).emit();
+        self.motion_callers.retain(|motion_caller| motion_caller != &account_id);
+
+        self.motion_callers.clone()
+    }
+
+    pub fn motion_callers(&self) -> Vec<AccountId> {
+        self.motion_callers.clone()
+    }
+
+    pub fn is_motion_caller(&self) -> bool {
+        self.motion_callers.contains(&env::predecessor_account_id())
+    }
+
     #[private]
     pub fn internal_post_assert_motion(&mut self, category: Option<String>, description: String, value: Option<VoteValue>, quorum: Option<u32>, pool: Option<Vec<AccountId>>) -> Motion {
         let motion_id = timestamp().to_string();
@@ -102,7 +137,6 @@ impl Contract {
             quorum,
         };
 
-        let voter = Voter::vote(self.voters.clone(), &env::signer_account_id(), value);
         let mut voters = UnorderedSet::new(
             StorageKey::Voters {
                 motion_id: motion_id.clone(),
@@ -110,7 +144,10 @@ impl Contract {
             .try_to_vec()
             .unwrap(),
         );
-        voters.insert(&voter);
+        // a motion caller puts the motion up for its users, whoever signed for them casts no vote
+        if !self.is_motion_caller() {
+            voters.insert(&Voter::vote(self.voters.clone(), &env::signer_account_id(), value));
+        }
         self.motions.insert(&motion);
         self.voters_per_motion.insert(&motion_id, &voters);
 
@@ -168,3 +205,36 @@ impl Contract {
         }        
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    #[test]
+    fn motion_caller_puts_a_motion_up_for_an_unregistered_signer() {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(accounts(1))
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1);
+        testing_env!(builder.build());
+
+        let mut contract = Contract::new(accounts(1).into());
+        contract.motion_callers = vec![accounts(3).into()];
+
+        // the main contract opens a bet's motion on behalf of whoever settled the bet
+        builder
+            .signer_account_id(accounts(4))
+            .predecessor_account_id(accounts(3));
+        testing_env!(builder.build());
+
+        let motion = contract.motion(Some("bet".to_string()), "bet abc".to_string(), Some(true), None, None);
+
+        assert_eq!(motion.status, MotionStatus::OPEN);
+        assert!(contract.voters.iter().all(|voter| voter.account_id != accounts(4).to_string()));
+        assert_eq!(contract.voters_per_motion.get(&motion.motion_id).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Proposes the next owner, replacing any earlier proposal.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner_id: AccountId) -> Option<AccountId> {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(env::is_valid_account_id(new_owner_id.as_bytes()), "Invalid account {}.", new_owner_id);
+        assert!(new_owner_id != self.owner_id, "{} already owns the contract.", new_owner_id);
+
+        EventKind::OwnershipProposed(vec![OwnershipProposedData {
+            owner_id: &self.owner_id,
+            proposed_owner_id: &new_owner_id,
+        }])
+        .emit();
+        self.proposed_owner_id = Some(new_owner_id);
+
+        self.proposed_owner_id.clone()
+    }
+
+    /// Takes over the contract, and with it every owner-only call: the motion callers, the ownership
+    /// proposals and `migrate`. Voter seats stay as they are, the old owner keeps any seat it holds
+    /// and a new owner who wants one registers and gets voted in like anyone else.
+    #[payable]
+    pub fn accept_ownership(&mut self) -> AccountId {
+        assert_one_yocto();
+        let new_owner_id = env::predecessor_account_id();
+        assert!(
+            self.proposed_owner_id.as_ref() == Some(&new_owner_id),
+            "Only the proposed owner can accept ownership.",
+        );
+
+        EventKind::OwnershipTransferred(vec![OwnershipTransferredData {
+            old_owner_id: &self.owner_id,
+            new_owner_id: &new_owner_id,
+        }])
+        .emit();
+        self.owner_id = new_owner_id;
+        self.proposed_owner_id = None;
+
+        self.owner_id.clone()
+    }
+
+    #[payable]
+    pub fn cancel_ownership_proposal(&mut self) {
+        assert_one_yocto();
+        self.assert_owner();
+        let proposed_owner_id = self.proposed_owner_id.take().expect("No owner proposed.");
+
+        EventKind::OwnershipProposalCancelled(vec![OwnershipProposalCancelledData {
+            owner_id: &self.owner_id,
+            proposed_owner_id: &proposed_owner_id,
+        }])
+        .emit();
+    }
+
+    pub fn owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    pub fn proposed_owner(&self) -> Option<AccountId> {
+        self.proposed_owner_id.clone()
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        assert!(env::predecessor_account_id() == self.owner_id, "Only the owner can do this.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    fn transferred_contract(builder: &mut VMContextBuilder) -> Contract {
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(accounts(1))
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1);
+        testing_env!(builder.build());
+        let mut contract = Contract::new(accounts(1).into());
+        contract.voters = vec![Voter {
+            account_id: accounts(1).into(),
+            vote: None,
+            status: VoterStatus::REGISTERED,
+        }];
+        contract.propose_owner(accounts(2).into());
+
+        builder
+            .signer_account_id(accounts(2))
+            .predecessor_account_id(accounts(2));
+        testing_env!(builder.build());
+        contract.accept_ownership();
+
+        contract
+    }
+
+    #[test]
+    fn new_owner_takes_the_owner_calls_but_no_seat() {
+        let mut builder = VMContextBuilder::new();
+        let mut contract = transferred_contract(&mut builder);
+
+        assert_eq!(contract.owner(), accounts(2).to_string());
+        assert_eq!(contract.add_motion_caller(accounts(3).into()), vec![accounts(3).to_string()]);
+        let seats: Vec<AccountId> = contract.voters.iter().map(|voter| voter.account_id.clone()).collect();
+        assert_eq!(seats, vec![accounts(1).to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can do this.")]
+    fn old_owner_loses_the_owner_calls() {
+        let mut builder = VMContextBuilder::new();
+        let mut contract = transferred_contract(&mut builder);
+
+        builder
+            .signer_account_id(accounts(1))
+            .predecessor_account_id(accounts(1));
+        testing_env!(builder.build());
+        contract.add_motion_caller(accounts(3).into());
+    }
+}
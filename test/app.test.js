@@ -57,6 +57,15 @@ describe(contractName, () => {
 
         await Promise.all([alice.account, mary.account, bob.account, john.account, jack.account]);
         [alice, mary, bob, john, jack].map(async accountObj => accountObj.account = await accountObj.account);
+
+        // stakes draw their storage on what the staker prepaid
+        await Promise.all([alice, mary, bob, john, jack].map(async accountObj => (await accountObj.account).functionCall({
+            contractId,
+            methodName: 'storage_deposit',
+            args: {},
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.5')
+        })));
     });
 
     test(`contract owner changes the stake pool`, async () => {
@@ -104,6 +113,118 @@ describe(contractName, () => {
             expect(true);
         }
     });
+    test(`contract owner updates the config`, async () => {
+        const config = await contractAccount.viewFunction(contractName, 'config');
+
+        await expect(contractAccount.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, min_stake: '0' } },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_INVALID min_stake/);
+        await expect(contractAccount.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, settlement_batch_size: 0 } },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_INVALID settlement_batch_size/);
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: ConfigAdmin/);
+
+        // a config admin tunes the numbers but leaves the voting contract and the default pool alone
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'grant_role',
+            args: { account_id: john.id, role: 'ConfigAdmin' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await expect(john.account.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, voting_contract: john.id } },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Owner/);
+        await expect(john.account.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, default_stake_pool: john.id } },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Owner/);
+        await john.account.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, keeper_fee: '0' } },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect((await contractAccount.viewFunction(contractName, 'config')).keeper_fee).toBe('0');
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'revoke_role',
+            args: { account_id: john.id, role: 'ConfigAdmin' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, refund_delay_epochs: config.refund_delay_epochs + 1 } },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect((await contractAccount.viewFunction(contractName, 'config')).refund_delay_epochs).toBe(config.refund_delay_epochs + 1);
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await contractAccount.viewFunction(contractName, 'config')).toEqual(config);
+    });
+    test(`storage is prepaid and withdrawn per NEP-145`, async () => {
+        const { min } = await bob.account.viewFunction(contractId, 'storage_balance_bounds');
+        expect(new BN(min).gt(new BN(0))).toBe(true);
+
+        const balance = await bob.account.viewFunction(contractId, 'storage_balance_of', { account_id: bob.id });
+        expect(balance.total).toBe(parseNearAmount('0.5'));
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'storage_withdraw',
+            args: { amount: parseNearAmount('0.1') },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        const { total } = await bob.account.viewFunction(contractId, 'storage_balance_of', { account_id: bob.id });
+        expect(total).toBe(parseNearAmount('0.4'));
+
+        await expect(contractAccount.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id: nanoid(),
+                stake_id: nanoid(),
+                prediction: 'Reduction',
+                position: 'Lay',
+                person: 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
+                end: (Date.now() + 8.64e+7) * 1000000
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_NOT_REGISTERED/);
+    });
     test(`contract admin changes the admin metadata`, async () => {
         const newAdmins = [mary.id, alice.id];
 
@@ -166,6 +287,129 @@ describe(contractName, () => {
             expect(true);
         }
     });
+    test(`admins hold the admin roles and the owner grants and revokes the rest`, async () => {
+        expect(await bob.account.viewFunction(contractId, 'has_role', { account_id: alice.id, role: 'Moderator' })).toBe(true);
+        expect(await bob.account.viewFunction(contractId, 'has_role', { account_id: mary.id, role: 'Moderator' })).toBe(false);
+        expect(await bob.account.viewFunction(contractId, 'roles_of', { account_id: contractId })).toContain('Owner');
+
+        await expect(alice.account.functionCall({
+            contractId,
+            methodName: 'grant_role',
+            args: { account_id: bob.id, role: 'Keeper' },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Owner/);
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'grant_role',
+            args: { account_id: bob.id, role: 'Keeper' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.account.viewFunction(contractId, 'role_members', { role: 'Keeper' })).toEqual([bob.id]);
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'revoke_role',
+            args: { account_id: bob.id, role: 'Keeper' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.account.viewFunction(contractId, 'has_role', { account_id: bob.id, role: 'Keeper' })).toBe(false);
+    });
+    test(`a pauser stops new stakes and only the owner lifts it`, async () => {
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'grant_role',
+            args: { account_id: mary.id, role: 'Pauser' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await mary.account.functionCall({
+            contractId,
+            methodName: 'pause',
+            args: { methods: ['NewStake'] },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect((await bob.account.viewFunction(contractId, 'paused')).map(({ method }) => method)).toEqual(['NewStake']);
+
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id: nanoid(),
+                stake_id: nanoid(),
+                prediction: 'Reduction',
+                position: 'Lay',
+                person: 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
+                end: (Date.now() + 8.64e+7) * 1000000
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_PAUSED method: NewStake/);
+        await expect(mary.account.functionCall({
+            contractId,
+            methodName: 'unpause',
+            args: { methods: ['NewStake'] },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Owner/);
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'unpause',
+            args: { methods: ['NewStake'] },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.account.viewFunction(contractId, 'paused')).toEqual([]);
+    });
+    test(`ownership moves only once the proposed owner accepts`, async () => {
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'propose_owner',
+            args: { new_owner_id: alice.id },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'accept_ownership',
+            args: {},
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED proposed_owner_id/);
+        expect(await bob.account.viewFunction(contractId, 'owner')).toBe(contractId);
+
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'accept_ownership',
+            args: {},
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.account.viewFunction(contractId, 'has_role', { account_id: alice.id, role: 'Owner' })).toBe(true);
+        expect(await bob.account.viewFunction(contractId, 'has_role', { account_id: contractId, role: 'Owner' })).toBe(false);
+
+        // hand it back for the rest of the suite
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'propose_owner',
+            args: { new_owner_id: contractId },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'accept_ownership',
+            args: {},
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.account.viewFunction(contractId, 'owner')).toBe(contractId);
+        expect(await bob.account.viewFunction(contractId, 'proposed_owner')).toBe(null);
+    });
     test(`ended bets`, async () => {
         let usedPersons = [];
         let unusedPersons = [
@@ -216,22 +460,141 @@ describe(contractName, () => {
         }));
     });
     test(`gentlemans bet`, async () => {
+        const bet_id = nanoid(),
+            offer_stake_id = nanoid(),
+            stake_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my';
+
         await bob.account.functionCall({
             contractId,
             methodName: 'new_stake',
             args: {
-                bet_id: nanoid(),
-                stake_id: nanoid(),
+                bet_id,
+                stake_id: offer_stake_id,
                 prediction: 'Reduction',
                 position: 'Lay',
-                person: 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
-                end: (Date.now() + 8.64e+7) * 1000000,
-                gentlemans: true,
+                person,
+                end,
+                gentlemans: { counterparty: alice.id, expires: end },
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        // a public back on the same bet never touches the offer
+        await john.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        let [offer] = await bob.account.viewFunction(contractId, 'gentlemans_offers', { stake_ids: [ offer_stake_id ] });
+        expect(offer.status).toBe('Open');
+        expect(offer.amount).toBe((await bob.account.viewFunction(contractId, 'stakes_for_bets', { bet_ids: [ bet_id ] }))
+            .find(stake => stake.stake_id === offer_stake_id).unmatched);
+
+        await expect(john.account.functionCall({
+            contractId,
+            methodName: 'accept_gentlemans_offer',
+            args: { offer_stake_id, stake_id: nanoid() },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_NOT_COUNTERPARTY/);
+
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'accept_gentlemans_offer',
+            args: { offer_stake_id, stake_id },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        [offer] = await bob.account.viewFunction(contractId, 'gentlemans_offers', { stake_ids: [ offer_stake_id ] });
+        expect(offer).toEqual(expect.objectContaining({ status: 'Accepted', taker: stake_id }));
+
+        const matches = await bob.account.viewFunction(contractId, 'matches_for_stake', { stake_id });
+        expect(matches.length).toBe(1);
+        expect(matches[0]).toEqual(expect.objectContaining({ back_stake_id: stake_id, lay_stake_id: offer_stake_id }));
+    });
+    test(`an invite code only works for the account that committed to it`, async () => {
+        const bet_id = nanoid(),
+            offer_stake_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
+            code = nanoid();
+        const sha256 = (data) => require('crypto').createHash('sha256').update(data).digest('base64');
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id: offer_stake_id,
+                prediction: 'Reduction',
+                position: 'Lay',
+                person,
+                end,
+                gentlemans: { invite_hash: sha256(code), expires: end },
             },
             gas: GAS,
             attachedDeposit: parseNearAmount('0.1')
         });
-        // test should be something to ensure the bet is not matched.
+        await expect(john.account.functionCall({
+            contractId,
+            methodName: 'accept_gentlemans_offer',
+            args: { offer_stake_id, stake_id: nanoid(), invite_code: code },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_NOT_COMMITTED/);
+
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'commit_gentlemans_invite',
+            args: { commitment: sha256(code + alice.id) },
+            gas: GAS
+        });
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'accept_gentlemans_offer',
+            args: { offer_stake_id, stake_id: nanoid(), invite_code: code },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        const [offer] = await bob.account.viewFunction(contractId, 'gentlemans_offers', { stake_ids: [ offer_stake_id ] });
+        expect(offer.status).toBe('Accepted');
+    });
+    test(`threshold predictions are validated and a bet keeps its prediction`, async () => {
+        const bet_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
+            prediction = { Threshold: { direction: 'Augmentation', cup_sizes: 2 } };
+
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: { Threshold: { direction: 'Augmentation', cup_sizes: 0 } }, position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_INVALID cup_sizes/);
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction, position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        await expect(alice.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'NoChange', position: 'Lay', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_INVALID prediction/);
+
+        const [bet] = await bob.account.viewFunction(contractId, 'bets', { bet_ids: [ bet_id ] });
+        expect(bet.prediction).toEqual(prediction);
     });
     test(`single bet`, async () => {
         const bet_id = nanoid(),
@@ -371,60 +734,267 @@ describe(contractName, () => {
         
         console.log(JSON.stringify(state), null, '  ');
     });
-    test(`cancel bet (full)`, async () => {
+    test(`backs and lays only match at compatible odds`, async () => {
         const bet_id = nanoid(),
-            stake_id = nanoid(),
-            end = (Date.now() + 8.64e+7) * 1000000;
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa';
 
         await bob.account.functionCall({
             contractId,
             methodName: 'new_stake',
-            args: {
-                bet_id,
-                stake_id,
-                prediction: 'Reduction',
-                position: 'Back',
-                person: 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa',
-                end
-            },
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end, odds: 300 },
             gas: GAS,
             attachedDeposit: parseNearAmount('0.1')
         });
-
-        const response = await bob.account.functionCall({
+        // offers 2.50 to a backer asking for 3.00, nothing trades
+        await alice.account.functionCall({
             contractId,
-            methodName: 'cancel_stake',
-            args: {
-                bet_id,
-                stake_id,
-            },
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end, odds: 250 },
             gas: GAS,
-            attachedDeposit: parseNearAmount('0.1')
+            attachedDeposit: parseNearAmount('0.2')
         });
 
-        expect(Buffer.from(response.status.SuccessValue, 'base64').toString()).toEqual(expect.stringMatching(/full cancellation pending/));
-        console.log(JSON.stringify(Buffer.from(response.status.SuccessValue, 'base64').toString()), null, '  ');
-    });
-    test(`cancel bet (partial)`, async () => {
-        const bet_id = nanoid(),
-            stake_id = nanoid(),
-            end = (Date.now() + 8.64e+7) * 1000000;
+        let [prices] = await bob.account.viewFunction(contractId, 'prices', { bet_ids: [ bet_id ] });
+        expect(prices.back[0][0]).toBe(250);
+        expect(prices.lay[0][0]).toBe(300);
 
-        await bob.account.functionCall({
+        // offers 3.50, trades at the backer's 3.00 and covers the whole back
+        await john.account.functionCall({
             contractId,
             methodName: 'new_stake',
-            args: {
-                bet_id,
-                stake_id,
-                prediction: 'Reduction',
-                position: 'Back',
-                person: 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa',
-                end
-            },
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end, odds: 350 },
             gas: GAS,
-            attachedDeposit: parseNearAmount('0.1')
+            attachedDeposit: parseNearAmount('0.3')
         });
-        await alice.account.functionCall({
+
+        const state = await bob.account.viewFunction(contractId, 'stakes_for_bets', { bet_ids: [ bet_id ] });
+        const back = state.find(stake => stake.position === 'Back');
+        const lay = state.find(stake => stake.position === 'Lay' && stake.odds === 350);
+
+        expect(back.unmatched).toBe('0');
+
+        const matches = await bob.account.viewFunction(contractId, 'matches_for_bet', { bet_id });
+        expect(matches.length).toBe(1);
+        expect(matches[0]).toEqual(expect.objectContaining({
+            back_stake_id: back.stake_id,
+            lay_stake_id: lay.stake_id,
+            amount: back.amount,
+            odds: 300,
+        }));
+        expect(new BN(matches[0].liability).eq(new BN(back.amount).muln(2))).toBe(true);
+
+        const stakeMatches = await bob.account.viewFunction(contractId, 'matches_for_stake', { stake_id: lay.stake_id });
+        expect(stakeMatches).toEqual(matches);
+        const accountMatches = await bob.account.viewFunction(contractId, 'matches_for_account', { account_id: john.id });
+        expect(accountMatches).toEqual(expect.arrayContaining(matches));
+
+        [prices] = await bob.account.viewFunction(contractId, 'prices', { bet_ids: [ bet_id ] });
+        expect(prices.lay).toEqual([]);
+    });
+    test(`stakes that rest through a matching pause match through rematch`, async () => {
+        const bet_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa';
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'pause',
+            args: { methods: ['Matching'] },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end, odds: 300 },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end, odds: 300 },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.2')
+        });
+        expect(await bob.account.viewFunction(contractId, 'matches_for_bet', { bet_id })).toEqual([]);
+        await expect(jack.account.functionCall({
+            contractId,
+            methodName: 'rematch',
+            args: { bet_id },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_PAUSED method: Matching/);
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'unpause',
+            args: { methods: ['Matching'] },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await jack.account.functionCall({
+            contractId,
+            methodName: 'rematch',
+            args: { bet_id },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        const matches = await bob.account.viewFunction(contractId, 'matches_for_bet', { bet_id });
+        expect(matches.length).toBe(1);
+        const state = await bob.account.viewFunction(contractId, 'stakes_for_bets', { bet_ids: [ bet_id ] });
+        expect(state.find(stake => stake.position === 'Back').unmatched).toBe('0');
+    });
+    test(`pro-rata bets share a back across every lay at the price`, async () => {
+        const bet_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa';
+
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end, matching_policy: 'ProRata' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.2')
+        });
+        await john.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.4')
+        });
+        // first in line under FIFO would take the whole back
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.15')
+        });
+
+        const [bet] = await bob.account.viewFunction(contractId, 'bets', { bet_ids: [ bet_id ] });
+        expect(bet.matching_policy).toBe('ProRata');
+
+        const state = await bob.account.viewFunction(contractId, 'stakes_for_bets', { bet_ids: [ bet_id ] });
+        state.filter(stake => stake.position === 'Lay').forEach(lay => {
+            expect(new BN(lay.unmatched).lt(new BN(lay.amount))).toBe(true);
+            expect(new BN(lay.unmatched).gtn(0)).toBe(true);
+        });
+
+        const matches = await bob.account.viewFunction(contractId, 'matches_for_bet', { bet_id });
+        expect(matches.length).toBe(2);
+    });
+    test(`a matched bet emits NEP-297 events`, async () => {
+        const bet_id = nanoid(),
+            backStakeId = nanoid(),
+            layStakeId = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000;
+        const events = (outcome) => outcome.receipts_outcome
+            .flatMap(receipt => receipt.outcome.logs)
+            .filter(log => log.startsWith('EVENT_JSON:'))
+            .map(log => JSON.parse(log.slice('EVENT_JSON:'.length)));
+
+        const created = events(await john.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id: backStakeId,
+                prediction: 'Reduction',
+                position: 'Back',
+                person: 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
+                end
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        }));
+        expect(created.map(event => event.event)).toEqual(['bet_created', 'stake_created']);
+        expect(created.every(event => event.standard === 'titstake' && event.version)).toBe(true);
+        expect(created[0].data[0]).toMatchObject({ bet_id, creator: john.id });
+        expect(created[1].data[0]).toMatchObject({ stake_id: backStakeId, bet_id, staker: john.id, amount: parseNearAmount('0.1') });
+
+        const matched = events(await jack.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id: layStakeId,
+                prediction: 'Reduction',
+                position: 'Lay',
+                person: 'bafybeihdzk6jvzkt2d3ekxkpkgdvtl3zryzeotsdlku7my6tncxxlyx3my',
+                end
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        }));
+        expect(matched.map(event => event.event)).toEqual(['stake_created', 'stake_matched']);
+        expect(matched[1].data[0]).toMatchObject({
+            bet_id,
+            back_stake_id: backStakeId,
+            lay_stake_id: layStakeId,
+            backer: john.id,
+            layer: jack.id,
+            amount: parseNearAmount('0.1'),
+            liability: parseNearAmount('0.1'),
+        });
+    });
+    test(`cancel bet (full)`, async () => {
+        const bet_id = nanoid(),
+            stake_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000;
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id,
+                prediction: 'Reduction',
+                position: 'Back',
+                person: 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa',
+                end
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        const response = await bob.account.functionCall({
+            contractId,
+            methodName: 'cancel_stake',
+            args: {
+                bet_id,
+                stake_id,
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        expect(Buffer.from(response.status.SuccessValue, 'base64').toString()).toEqual(expect.stringMatching(/full cancellation pending/));
+        console.log(JSON.stringify(Buffer.from(response.status.SuccessValue, 'base64').toString()), null, '  ');
+    });
+    test(`cancel bet (partial)`, async () => {
+        const bet_id = nanoid(),
+            stake_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000;
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id,
+                prediction: 'Reduction',
+                position: 'Back',
+                person: 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa',
+                end
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        await alice.account.functionCall({
             contractId,
             methodName: 'new_stake',
             args: {
@@ -533,7 +1103,12 @@ describe(contractName, () => {
             attachedDeposit: parseNearAmount('0.1')
         });
 
-        const response = await bob.account.functionCall({
+        const [cancelledStake] = await bob.account.viewFunction(contractId, 'cancelled_stakes', {
+            stake_ids: [stake_id]
+        });
+        expect(cancelledStake.status).toBe('Unstaked');
+
+        await bob.account.functionCall({
             contractId,
             methodName: 'refund_cancelled_stake',
             args: {
@@ -541,12 +1116,90 @@ describe(contractName, () => {
             },
             gas: GAS,
         });
+
+        const [refundedStake] = await bob.account.viewFunction(contractId, 'cancelled_stakes', {
+            stake_ids: [stake_id]
+        });
+        expect(refundedStake.status).toBe('Refunded');
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'refund_cancelled_stake',
+            args: {
+                stake_id,
+            },
+            gas: GAS,
+        })).rejects.toThrow(/ERR_REFUNDED/);
         
         const accountBalanceAfter = formatNearAmount((await getAccountBalance(bob.id)).total);
         console.log({accountBalanceBefore});
         console.log({accountBalanceAfter});
         expect(Number(accountBalanceBefore)).toBeCloseTo(Number(accountBalanceAfter), 1);
     });
+    test(`settling a bet without a motion fails`, async () => {
+        const bet_id = nanoid();
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id: nanoid(),
+                prediction: 'Reduction',
+                position: 'Back',
+                person: 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa',
+                end: (Date.now() + 8.64e+7) * 1000000
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'settle_bet',
+            args: {
+                bet_id,
+            },
+            gas: GAS,
+        })).rejects.toThrow(/ERR_NO_MOTION/);
+
+        const settlement = await bob.account.viewFunction(contractId, 'settlement', { bet_id });
+        expect(settlement).toBeNull();
+    });
+    test(`stakes keep the pool they were deposited in`, async () => {
+        const bet_id = nanoid();
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: {
+                bet_id,
+                stake_id: nanoid(),
+                prediction: 'Reduction',
+                position: 'Lay',
+                person: 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa',
+                end: (Date.now() + 8.64e+7) * 1000000
+            },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        const [stake] = await bob.account.viewFunction(contractId, 'stakes_for_bets', {
+            bet_ids: [ bet_id ]
+        });
+        expect(stake.pool).toBe(stakePool);
+
+        const pools = await bob.account.viewFunction(contractId, 'pools');
+        const pool = pools.find(({ pool }) => pool === stakePool);
+        expect(new BN(pool.staked).gte(new BN(stake.amount))).toBe(true);
+        expect(new BN(pool.shares).gte(new BN(stake.amount))).toBe(true);
+
+        // the deposit recorded what bob held before it, once per epoch
+        const balances = await bob.account.viewFunction(contractId, 'account_balances', { account_id: bob.id });
+        expect(balances.length).toBeGreaterThan(0);
+        const [epoch] = balances[balances.length - 1];
+        expect(await bob.account.viewFunction(contractId, 'balance_at_epoch', { account_id: bob.id, epoch }))
+            .toBe(balances[balances.length - 1][1]);
+    });
     test(`view persons`, async () => {
         const persons = await bob.account.viewFunction(contractId, 'persons');
         expect(persons.length).toBe(13);
@@ -567,6 +1220,300 @@ describe(contractName, () => {
         const persons = await bob.account.viewFunction(contractId, 'persons_count');
         expect(persons).toBe('13');
     });
+    test(`person records`, async () => {
+        const person = 'bafybei' + [...Array(52)].map(() => 'abcdefghijklmnopqrstuvwxyz234567'[Math.floor(Math.random() * 32)]).join('');
+
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'add_person',
+            args: { person: 'not-a-cid', alias: 'Nobody' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        })).rejects.toThrow(/ERR_INVALID person/);
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id: nanoid(), stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person: 'not-a-cid', end: (Date.now() + 8.64e+7) * 1000000 },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_INVALID person/);
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'add_person',
+            args: { person, alias: 'Nobody' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        });
+        await expect(john.account.functionCall({
+            contractId,
+            methodName: 'update_person',
+            args: { person, alias: 'Someone' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Moderator/);
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'update_person',
+            args: { person, alias: 'Someone' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        });
+        await alice.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id: nanoid(), stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end: (Date.now() + 8.64e+7) * 1000000 },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+
+        const record = await bob.account.viewFunction(contractId, 'person', { person });
+        expect(record).toEqual(expect.objectContaining({ alias: 'Someone', creator: bob.id, status: 'Active', bet_count: '1' }));
+
+        // a moderator's deactivation is not the creator's to undo
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'update_person',
+            args: { person, status: 'Inactive' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        });
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'update_person',
+            args: { person, status: 'Active' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Moderator/);
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'update_person',
+            args: { person, status: 'Active' },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        });
+        expect((await bob.account.viewFunction(contractId, 'person', { person })).moderated).toBe(false);
+    });
+    test(`a linked person approves bets and opts out`, async () => {
+        const person = 'bafybei' + [...Array(52)].map(() => 'abcdefghijklmnopqrstuvwxyz234567'[Math.floor(Math.random() * 32)]).join(''),
+            bet_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000;
+
+        await jack.account.functionCall({
+            contractId,
+            methodName: 'add_person',
+            args: { person },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        });
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'link_person_account',
+            args: { person, account_id: mary.id },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+
+        await expect(jack.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_NOT_APPROVED/);
+
+        await jack.account.functionCall({
+            contractId,
+            methodName: 'request_bet_approval',
+            args: { bet_id, person, prediction: 'Reduction', end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.01')
+        });
+        await expect(jack.account.functionCall({
+            contractId,
+            methodName: 'approve_bet',
+            args: { bet_id },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED/);
+        await mary.account.functionCall({
+            contractId,
+            methodName: 'approve_bet',
+            args: { bet_id },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        await jack.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        expect((await bob.account.viewFunction(contractId, 'bets', { person })).map(bet => bet.bet_id)).toEqual([bet_id]);
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'void_person_bets',
+            args: { person },
+            gas: GAS
+        })).rejects.toThrow(/ERR_NOT_WITHDRAWN/);
+
+        await mary.account.functionCall({
+            contractId,
+            methodName: 'opt_out',
+            args: { person },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        // the only bet was voided by the opt out, nothing is left for later pages
+        expect(await bob.account.functionCall({
+            contractId,
+            methodName: 'void_person_bets',
+            args: { person },
+            gas: GAS
+        }).then(({ status }) => JSON.parse(Buffer.from(status.SuccessValue, 'base64').toString()))).toEqual([]);
+
+        const record = await bob.account.viewFunction(contractId, 'person', { person });
+        expect(record.status).toBe('OptedOut');
+        const settlement = await bob.account.viewFunction(contractId, 'settlement', { bet_id });
+        expect(settlement.winner).toBeNull();
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_BET_CLOSED/);
+    });
+    test(`an admin takes a bet down`, async () => {
+        const bet_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa';
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'take_down',
+            args: { target: { Bet: bet_id }, reason: 'Posted without consent' },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED role: Moderator/);
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'take_down',
+            args: { target: { Bet: bet_id }, reason: 'Posted without consent' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+
+        const takedowns = await bob.account.viewFunction(contractId, 'takedowns', { limit: 100 });
+        expect(takedowns[takedowns.length - 1]).toEqual(expect.objectContaining({
+            target: { Bet: bet_id },
+            reason: 'Posted without consent',
+            status: 'Enacted',
+            voided: [ bet_id ],
+        }));
+        const settlement = await bob.account.viewFunction(contractId, 'settlement', { bet_id });
+        expect(settlement.winner).toBeNull();
+        await expect(alice.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Lay', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_BET_CLOSED/);
+    });
+    test(`a voided bet settles one batch per call`, async () => {
+        const bet_id = nanoid(),
+            end = (Date.now() + 8.64e+7) * 1000000,
+            person = 'bafybeigwqcbb5qj2463brh627ktivsgdeiikk4w2o6dgipgqoq4revy4fa';
+        const config = await contractAccount.viewFunction(contractName, 'config');
+
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'new_stake',
+            args: { bet_id, stake_id: nanoid(), prediction: 'Reduction', position: 'Back', person, end },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'take_down',
+            args: { target: { Bet: bet_id }, reason: 'Posted without consent' },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        let settlement = await bob.account.viewFunction(contractId, 'settlement', { bet_id });
+        expect(settlement).toEqual(expect.objectContaining({ step: 'Pending', stakes: '0', status: null }));
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config: { ...config, settlement_batch_size: 1 } },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        // the one stake uses up the batch, tallying it waits for the next call
+        await bob.account.functionCall({
+            contractId,
+            methodName: 'settle_bet',
+            args: { bet_id },
+            gas: GAS
+        });
+        settlement = await bob.account.viewFunction(contractId, 'settlement', { bet_id });
+        expect(settlement).toEqual(expect.objectContaining({ step: 'Resolved', progress: '0', stakes: '1', winner: null }));
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'update_config',
+            args: { config },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+    });
+    test(`anyone can put expired bets to the vote`, async () => {
+        const fees = await bob.account.viewFunction(contractId, 'protocol_fees');
+        expect(new BN(fees).gte(new BN(0))).toBe(true);
+
+        const receipt = await bob.account.functionCall({
+            contractId,
+            methodName: 'process_expired_bets',
+            args: { limit: 5 },
+            gas: GAS
+        });
+        const count = JSON.parse(Buffer.from(receipt.status.SuccessValue, 'base64').toString());
+        expect(Number(count)).toBeLessThanOrEqual(5);
+    });
+    test(`only the owner stages an upgrade and it waits on its motion`, async () => {
+        const code = Buffer.from('not really wasm');
+        const hash = require('crypto').createHash('sha256').update(code).digest('hex');
+
+        await expect(bob.account.functionCall({
+            contractId,
+            methodName: 'stage_upgrade',
+            args: { code: code.toString('base64') },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        })).rejects.toThrow(/ERR_NOT_AUTHORIZED/);
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'stage_upgrade',
+            args: { code: code.toString('base64') },
+            gas: GAS,
+            attachedDeposit: parseNearAmount('0.1')
+        });
+        const staged = await bob.account.viewFunction(contractId, 'staged_upgrade');
+        expect(staged.hash).toBe(hash);
+        expect(staged.staged_by).toBe(contractId);
+        expect(staged.size).toBe(String(code.length));
+    });
     test(`view total bets`, async () => {
         const bets = await bob.account.viewFunction(contractId, 'bets_count');
         expect(bets);
@@ -530,4 +530,82 @@ describe('deploy contract ' + contractId, () => {
         expect(motion.motion_id).toBeTruthy();
         expect(motion.status).toBe('OPEN');
     });
+    test(`The owner proposes Alice as owner, Bob cannot accept and the owner cancels.`, async () => {
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'propose_owner',
+            args: { new_owner_id: aliceId },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.viewFunction(contractId, 'proposed_owner')).toBe(aliceId);
+
+        await expect(bob.functionCall({
+            contractId,
+            methodName: 'accept_ownership',
+            args: {},
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/Only the proposed owner can accept ownership/);
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'cancel_ownership_proposal',
+            args: {},
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.viewFunction(contractId, 'proposed_owner')).toBe(null);
+        expect(await bob.viewFunction(contractId, 'owner')).toBe(contractId);
+    });
+    test(`A motion caller puts a motion up without a voter seat until the owner removes it.`, async () => {
+        const caller = unnamedUserAccounts[4];
+        const motionArgs = {
+            category: 'bet',
+            description: `${caller.accountId} is put up by a motion caller.`,
+            pool: [aliceId, bobId],
+        };
+        await expect(caller.functionCall({
+            contractId,
+            methodName: 'motion',
+            args: motionArgs,
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/You must be registered/);
+
+        await expect(alice.functionCall({
+            contractId,
+            methodName: 'add_motion_caller',
+            args: { account_id: caller.accountId },
+            gas: GAS,
+            attachedDeposit: 1
+        })).rejects.toThrow(/Only the owner can do this/);
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'add_motion_caller',
+            args: { account_id: caller.accountId },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.viewFunction(contractId, 'motion_callers')).toEqual([caller.accountId]);
+
+        const receipt = await caller.functionCall({
+            contractId,
+            methodName: 'motion',
+            args: motionArgs,
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        const motion = JSON.parse(Buffer.from(receipt.status.SuccessValue, 'base64').toString());
+        expect(motion.status).toBe('OPEN');
+
+        await contractAccount.functionCall({
+            contractId,
+            methodName: 'remove_motion_caller',
+            args: { account_id: caller.accountId },
+            gas: GAS,
+            attachedDeposit: 1
+        });
+        expect(await bob.viewFunction(contractId, 'motion_callers')).toEqual([]);
+    });
 });